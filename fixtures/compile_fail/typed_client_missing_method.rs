@@ -0,0 +1,8 @@
+// the typed client only has the methods listed for its version, no version has a `Delete` rpc
+use protos::vector_service::typed_vector_service_client::{api_versions, VectorServiceClient};
+
+async fn delete(mut client: VectorServiceClient<api_versions::V1>) {
+    let _ = client.delete(()).await;
+}
+
+fn main() {}
@@ -0,0 +1,91 @@
+// the body of every `<version>_adapter` target, `fuzz_adapter!(v1, V1)` fuzzes V1. the first byte picks
+// what the rest is decoded as: the version's requests, served through its adapter with the same overrides
+// as `serve`, its responses, upgraded as its clients do, or the inner messages downgraded to it.
+
+use std::sync::OnceLock;
+
+use tokio::runtime::Runtime;
+
+pub fn runtime() -> &'static Runtime {
+    static RUNTIME: OnceLock<Runtime> = OnceLock::new();
+    RUNTIME.get_or_init(|| {
+        tokio::runtime::Builder::new_current_thread()
+            .build()
+            .unwrap()
+    })
+}
+
+macro_rules! fuzz_adapter {
+    ($version:ident, $variant:ident) => {
+        use libfuzzer_sys::fuzz_target;
+        use prost::Message;
+        use protos::api::$version;
+        use protos::api::$version::vector_service_server::VectorService;
+        use protos::api_versions::$variant;
+        use protos::conversions::{kinds, FromInner, ToInner};
+        use protos::vector_service as inner;
+        use protos::vector_service::vector_service_server::VersionAdapter;
+        use tonic::Request;
+        use versioning_grpc::{overrides, VectorHandler};
+
+        fuzz_target!(|data: &[u8]| {
+            let Some((target, message)) = data.split_first() else {
+                return;
+            };
+            let adapter = VersionAdapter::new(
+                VectorHandler {
+                    name: "fuzz".to_string(),
+                },
+                overrides(),
+            );
+            $crate::adapter::runtime().block_on(async {
+                // errors are fine, only panics are findings
+                match target % 8 {
+                    0 => {
+                        if let Ok(request) = $version::PrintRequest::decode(message) {
+                            let _ = adapter.print(Request::new(request)).await;
+                        }
+                    }
+                    1 => {
+                        if let Ok(request) = $version::SumRequest::decode(message) {
+                            let _ = adapter.sum(Request::new(request)).await;
+                        }
+                    }
+                    2 => {
+                        if let Ok(response) = $version::PrintResponse::decode(message) {
+                            let _ = <$variant as ToInner<kinds::PrintResponse>>::to_inner(response);
+                        }
+                    }
+                    3 => {
+                        if let Ok(response) = $version::SumResponse::decode(message) {
+                            let _ = <$variant as ToInner<kinds::SumResponse>>::to_inner(response);
+                        }
+                    }
+                    4 => {
+                        if let Ok(request) = inner::PrintRequest::decode(message) {
+                            let _ =
+                                <$variant as FromInner<kinds::PrintRequest>>::from_inner(request);
+                        }
+                    }
+                    5 => {
+                        if let Ok(request) = inner::SumRequest::decode(message) {
+                            let _ = <$variant as FromInner<kinds::SumRequest>>::from_inner(request);
+                        }
+                    }
+                    6 => {
+                        if let Ok(response) = inner::PrintResponse::decode(message) {
+                            let _ =
+                                <$variant as FromInner<kinds::PrintResponse>>::from_inner(response);
+                        }
+                    }
+                    _ => {
+                        if let Ok(response) = inner::SumResponse::decode(message) {
+                            let _ =
+                                <$variant as FromInner<kinds::SumResponse>>::from_inner(response);
+                        }
+                    }
+                }
+            });
+        });
+    };
+}
@@ -0,0 +1,8 @@
+// fuzzes V1's adapter and conversions, see `adapter.rs`. run with `cargo fuzz run v1_adapter` from the
+// repository root.
+#![no_main]
+
+#[macro_use]
+mod adapter;
+
+fuzz_adapter!(v1, V1);
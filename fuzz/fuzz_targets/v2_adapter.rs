@@ -0,0 +1,8 @@
+// fuzzes V2's adapter and conversions, see `adapter.rs`. run with `cargo fuzz run v2_adapter` from the
+// repository root.
+#![no_main]
+
+#[macro_use]
+mod adapter;
+
+fuzz_adapter!(v2, V2);
@@ -25,6 +25,15 @@ fn main() -> Result<(), Box<dyn std::error::Error>> {
             .file_descriptor_set_path(original_out_dir.join(format!("api.{version}.bin")))
             .compile(&[proto_path], &include_dirs)?;
     }
+    // tooling around the vector service, not versions of it
+    tonic_build::configure().out_dir("src").compile(
+        &[
+            Path::new(PROTO_DIR).join("admin/fault_admin.proto"),
+            Path::new(PROTO_DIR).join("recording/recording.proto"),
+        ],
+        &[Path::new(PROTO_DIR)],
+    )?;
+
     // remove unneeded google.api.rs file post-compile
     let google_api_path: &Path = "protos/src/google.api.rs".as_ref();
     if fs::metadata(google_api_path).is_ok() {
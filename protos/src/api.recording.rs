@@ -0,0 +1,41 @@
+// This file is @generated by prost-build.
+/// one call to a versioned service, as the server saw it.
+/// a recording is a file of these, each prefixed with its varint length
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct RecordedCall {
+    /// e.g. "V1"
+    #[prost(string, tag = "1")]
+    pub version: ::prost::alloc::string::String,
+    /// e.g. "Sum"
+    #[prost(string, tag = "2")]
+    pub method: ::prost::alloc::string::String,
+    #[prost(message, repeated, tag = "3")]
+    pub metadata: ::prost::alloc::vec::Vec<MetadataEntry>,
+    /// the gRPC-framed request body, as received
+    #[prost(bytes = "vec", tag = "4")]
+    pub request: ::prost::alloc::vec::Vec<u8>,
+    /// the gRPC-framed response body, as sent
+    #[prost(bytes = "vec", tag = "5")]
+    pub response: ::prost::alloc::vec::Vec<u8>,
+    /// the gRPC status code and message of the response
+    #[prost(int32, tag = "6")]
+    pub code: i32,
+    #[prost(string, tag = "7")]
+    pub message: ::prost::alloc::string::String,
+    /// when the call arrived, in microseconds since the unix epoch
+    #[prost(uint64, tag = "8")]
+    pub started_at_us: u64,
+    /// until the response was fully sent
+    #[prost(uint64, tag = "9")]
+    pub duration_us: u64,
+}
+#[allow(clippy::derive_partial_eq_without_eq)]
+#[derive(Clone, PartialEq, ::prost::Message)]
+pub struct MetadataEntry {
+    #[prost(string, tag = "1")]
+    pub key: ::prost::alloc::string::String,
+    /// binary values keep their base64 encoding
+    #[prost(string, tag = "2")]
+    pub value: ::prost::alloc::string::String,
+}
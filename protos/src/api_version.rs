@@ -0,0 +1,60 @@
+use std::fmt;
+
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::{Request, Response, Status};
+
+// metadata key the server uses to tell the caller which version actually served the call
+pub const API_VERSION_HEADER: &str = "x-api-version";
+
+/// Describes the public API version a call came through.
+///
+/// The version adapters insert it into the request extensions before handing the request to the
+/// inner `VectorService`, so the handler can vary behavior, logging or limits by version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct ApiVersion {
+    pub name: &'static str,
+    pub ordinal: u32,
+    pub deprecated: bool,
+}
+
+impl ApiVersion {
+    pub const fn new(name: &'static str, ordinal: u32, deprecated: bool) -> Self {
+        Self {
+            name,
+            ordinal,
+            deprecated,
+        }
+    }
+}
+
+impl fmt::Display for ApiVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name)
+    }
+}
+
+/// Returns the version the request was served through,
+/// or `None` if it did not pass through a version adapter.
+pub fn api_version<T>(request: &Request<T>) -> Option<ApiVersion> {
+    request.extensions().get::<ApiVersion>().copied()
+}
+
+fn echo_api_version(metadata: &mut MetadataMap, version: ApiVersion) {
+    metadata.insert(API_VERSION_HEADER, MetadataValue::from_static(version.name));
+}
+
+// echoes the served version on both successful responses and errors
+pub(crate) fn tag_api_version<T>(
+    result: Result<Response<T>, Status>,
+    version: ApiVersion,
+) -> Result<Response<T>, Status> {
+    result
+        .map(|mut response| {
+            echo_api_version(response.metadata_mut(), version);
+            response
+        })
+        .map_err(|mut status| {
+            echo_api_version(status.metadata_mut(), version);
+            status
+        })
+}
@@ -0,0 +1,306 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use serde::Deserialize;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{BoxFuture, Service, StdError};
+use tonic::Status;
+use tower_layer::Layer;
+
+use crate::capabilities::{versioned_call, Method};
+use crate::wrappers::vector_service_client::SupportedVersion;
+
+// metadata key an API key is sent in, bearer tokens go in `authorization`
+pub const API_KEY_HEADER: &str = "x-api-key";
+
+/// A version and method a principal may call, parsed from e.g. `V2.Sum`, `V1.*`, `*.Print` or `*`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Permission {
+    // any version or method when `None`
+    pub version: Option<SupportedVersion>,
+    pub method: Option<Method>,
+}
+
+impl Permission {
+    pub fn allows(&self, version: SupportedVersion, method: Method) -> bool {
+        self.version.is_none_or(|allowed| allowed == version)
+            && self.method.is_none_or(|allowed| allowed == method)
+    }
+}
+
+impl FromStr for Permission {
+    type Err = String;
+
+    fn from_str(permission: &str) -> Result<Self, Self::Err> {
+        let (version, method) = match permission {
+            "*" => ("*", "*"),
+            permission => permission
+                .split_once('.')
+                .ok_or_else(|| format!("{permission:?} is not `<version>.<method>`"))?,
+        };
+        Ok(Self {
+            version: match version {
+                "*" => None,
+                name => Some(
+                    SupportedVersion::from_name(name)
+                        .ok_or_else(|| format!("unknown version {name:?}"))?,
+                ),
+            },
+            method: match method {
+                "*" => None,
+                name => Some(
+                    Method::from_name(name).ok_or_else(|| format!("unknown method {name:?}"))?,
+                ),
+            },
+        })
+    }
+}
+
+impl fmt::Display for Permission {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let version = self
+            .version
+            .map_or("*", |version| version.api_version().name);
+        let method = self.method.map_or("*", Method::name);
+        write!(f, "{version}.{method}")
+    }
+}
+
+/// Who made a call. Authenticated calls carry it in their request extensions, see [`principal`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub name: String,
+    pub permissions: Vec<Permission>,
+}
+
+impl Principal {
+    pub fn allows(&self, version: SupportedVersion, method: Method) -> bool {
+        self.permissions
+            .iter()
+            .any(|permission| permission.allows(version, method))
+    }
+}
+
+/// Returns who made the call, or `None` if the server does not authenticate its callers.
+pub fn principal<T>(request: &tonic::Request<T>) -> Option<&Principal> {
+    request.extensions().get::<Principal>()
+}
+
+// a keys file is a list of `[[keys]]` tables
+#[derive(Debug, Deserialize)]
+struct KeysFile {
+    keys: Vec<KeyEntry>,
+}
+
+#[derive(Debug, Deserialize)]
+struct KeyEntry {
+    key: String,
+    principal: String,
+    allow: Vec<String>,
+}
+
+// the permissions of a bearer token are its space-separated `scope`
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    scope: String,
+}
+
+/// Checks the credentials of calls to the versioned vector services.
+///
+/// Callers send either an API key in `x-api-key`, or a JWT as `authorization: Bearer <token>`,
+/// signed by a key of the JWK set and not expired.
+#[derive(Debug, Clone, Default)]
+pub struct Authenticator {
+    keys: HashMap<String, Principal>,
+    jwks: Option<JwkSet>,
+}
+
+impl Authenticator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn with_key(mut self, key: impl Into<String>, principal: Principal) -> Self {
+        self.keys.insert(key.into(), principal);
+        self
+    }
+
+    /// Adds the keys of a TOML file of `[[keys]]` tables, each with a `key`, the `principal`
+    /// it authenticates, and the permissions it is `allow`ed.
+    pub fn with_keys_file(mut self, path: impl AsRef<Path>) -> Result<Self, StdError> {
+        let keys: KeysFile = toml::from_str(&std::fs::read_to_string(path)?)?;
+        for entry in keys.keys {
+            let permissions = entry
+                .allow
+                .iter()
+                .map(|permission| permission.parse())
+                .collect::<Result<_, String>>()?;
+            let principal = Principal {
+                name: entry.principal,
+                permissions,
+            };
+            self.keys.insert(entry.key, principal);
+        }
+        Ok(self)
+    }
+
+    pub fn with_jwks(mut self, jwks: JwkSet) -> Self {
+        self.jwks = Some(jwks);
+        self
+    }
+
+    /// Accepts bearer tokens signed by the keys of a JWKS JSON file.
+    pub fn with_jwks_file(self, path: impl AsRef<Path>) -> Result<Self, StdError> {
+        let jwks = serde_json::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(self.with_jwks(jwks))
+    }
+
+    pub fn layer(&self) -> AuthLayer {
+        AuthLayer {
+            authenticator: Some(Arc::new(self.clone())),
+        }
+    }
+
+    /// The principal a call comes from, if it may call `method` of `version`.
+    pub fn authorize(
+        &self,
+        headers: &HeaderMap,
+        version: SupportedVersion,
+        method: Method,
+    ) -> Result<Principal, Status> {
+        let principal = self.authenticate(headers)?;
+        match principal.allows(version, method) {
+            true => Ok(principal),
+            false => Err(Status::permission_denied(format!(
+                "{} may not call {} {}",
+                principal.name,
+                version.api_version().name,
+                method.name()
+            ))),
+        }
+    }
+
+    fn authenticate(&self, headers: &HeaderMap) -> Result<Principal, Status> {
+        let header = |name: &str| headers.get(name).and_then(|value| value.to_str().ok());
+        if let Some(key) = header(API_KEY_HEADER) {
+            return self
+                .keys
+                .get(key)
+                .cloned()
+                .ok_or_else(|| Status::unauthenticated("unknown API key"));
+        }
+        match header("authorization").and_then(|value| value.strip_prefix("Bearer ")) {
+            Some(token) => self.verify(token),
+            None => Err(Status::unauthenticated(
+                "an API key or a bearer token is required",
+            )),
+        }
+    }
+
+    fn verify(&self, token: &str) -> Result<Principal, Status> {
+        let invalid =
+            |reason: String| Status::unauthenticated(format!("invalid bearer token: {reason}"));
+        let jwks = self
+            .jwks
+            .as_ref()
+            .ok_or_else(|| Status::unauthenticated("bearer tokens are not accepted"))?;
+        let header = decode_header(token).map_err(|error| invalid(error.to_string()))?;
+        // a token without a key id can only be checked against a set of one key
+        let jwk = match (&header.kid, jwks.keys.as_slice()) {
+            (Some(kid), _) => jwks.find(kid),
+            (None, [jwk]) => Some(jwk),
+            (None, _) => None,
+        }
+        .ok_or_else(|| invalid("no matching key".to_string()))?;
+        let key = DecodingKey::from_jwk(jwk).map_err(|error| invalid(error.to_string()))?;
+        // a key that names its algorithm doesn't let the token pick another one
+        let algorithm = match jwk.common.key_algorithm {
+            Some(algorithm) => Algorithm::from_str(&algorithm.to_string())
+                .map_err(|error| invalid(error.to_string()))?,
+            None => header.alg,
+        };
+        let mut validation = Validation::new(algorithm);
+        validation.set_required_spec_claims(&["exp", "sub"]);
+        let claims = decode::<Claims>(token, &key, &validation)
+            .map_err(|error| invalid(error.to_string()))?
+            .claims;
+        let permissions = claims
+            .scope
+            .split_whitespace()
+            .map(|permission| permission.parse())
+            .collect::<Result<_, String>>()
+            .map_err(invalid)?;
+        Ok(Principal {
+            name: claims.sub,
+            permissions,
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct AuthLayer {
+    // every call goes through when `None`
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl AuthLayer {
+    pub fn disabled() -> Self {
+        Self {
+            authenticator: None,
+        }
+    }
+}
+
+impl<S> Layer<S> for AuthLayer {
+    type Service = AuthService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            authenticator: self.authenticator.clone(),
+        }
+    }
+}
+
+/// Authorizes every call to a versioned vector service, other services such as health checks stay open.
+#[derive(Debug, Clone)]
+pub struct AuthService<S> {
+    inner: S,
+    authenticator: Option<Arc<Authenticator>>,
+}
+
+impl<S, B> Service<Request<B>> for AuthService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: Request<B>) -> Self::Future {
+        let called = versioned_call(request.uri().path());
+        if let (Some(authenticator), Some((version, method))) = (&self.authenticator, called) {
+            match authenticator.authorize(request.headers(), version, method) {
+                Ok(principal) => {
+                    request.extensions_mut().insert(principal);
+                }
+                Err(status) => return Box::pin(async move { Ok(status.to_http()) }),
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
@@ -0,0 +1,300 @@
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, Weak};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures_util::future::{join_all, poll_fn};
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::codegen::{BoxFuture, Service, StdError};
+use tonic::transport::{Body, Channel, Endpoint, Uri};
+use tonic::Status;
+use tonic_health::pb::health_check_response::ServingStatus;
+use tonic_health::pb::health_client::HealthClient;
+use tonic_health::pb::HealthCheckRequest;
+
+use crate::policy::random_fraction;
+
+/// Where the endpoints of a [`BalancedChannel`] come from, resolved again on every health check round.
+#[tonic::async_trait]
+pub trait Resolver: Send + Sync + 'static {
+    async fn resolve(&self) -> Result<Vec<Uri>, StdError>;
+}
+
+/// A fixed list of endpoints.
+#[derive(Debug, Clone)]
+pub struct StaticResolver {
+    endpoints: Vec<Uri>,
+}
+
+impl StaticResolver {
+    pub fn new(endpoints: impl IntoIterator<Item = Uri>) -> Self {
+        Self {
+            endpoints: endpoints.into_iter().collect(),
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Resolver for StaticResolver {
+    async fn resolve(&self) -> Result<Vec<Uri>, StdError> {
+        Ok(self.endpoints.clone())
+    }
+}
+
+/// Every address a host name resolves to, all on the same port.
+#[derive(Debug, Clone)]
+pub struct DnsResolver {
+    host: String,
+    port: u16,
+}
+
+impl DnsResolver {
+    pub fn new(host: impl Into<String>, port: u16) -> Self {
+        Self {
+            host: host.into(),
+            port,
+        }
+    }
+}
+
+#[tonic::async_trait]
+impl Resolver for DnsResolver {
+    async fn resolve(&self) -> Result<Vec<Uri>, StdError> {
+        tokio::net::lookup_host((self.host.as_str(), self.port))
+            .await?
+            .map(|address| Ok(format!("http://{address}").parse()?))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BalanceStrategy {
+    #[default]
+    RoundRobin,
+    // picks the less loaded of two random endpoints, by calls in flight
+    PowerOfTwoChoices,
+}
+
+#[derive(Debug, Clone)]
+pub struct BalanceConfig {
+    pub strategy: BalanceStrategy,
+    pub health_check_interval: Duration,
+    pub health_check_timeout: Duration,
+}
+
+impl Default for BalanceConfig {
+    fn default() -> Self {
+        Self {
+            strategy: BalanceStrategy::default(),
+            health_check_interval: Duration::from_secs(5),
+            health_check_timeout: Duration::from_secs(1),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Backend {
+    uri: Uri,
+    channel: Channel,
+    healthy: AtomicBool,
+    in_flight: AtomicUsize,
+}
+
+impl Backend {
+    fn new(uri: Uri) -> Self {
+        Self {
+            channel: Endpoint::from(uri.clone()).connect_lazy(),
+            uri,
+            healthy: AtomicBool::new(false),
+            in_flight: AtomicUsize::new(0),
+        }
+    }
+}
+
+// counts a call against its endpoint until dropped, also when the caller abandons the call
+struct InFlight<'a>(&'a Backend);
+
+impl<'a> InFlight<'a> {
+    fn start(backend: &'a Backend) -> Self {
+        backend.in_flight.fetch_add(1, Ordering::Relaxed);
+        Self(backend)
+    }
+}
+
+impl Drop for InFlight<'_> {
+    fn drop(&mut self) {
+        self.0.in_flight.fetch_sub(1, Ordering::Relaxed);
+    }
+}
+
+#[derive(Debug)]
+struct Shared {
+    backends: RwLock<Vec<Arc<Backend>>>,
+    // the versioned service an endpoint must report as serving to receive calls
+    service: &'static str,
+    strategy: BalanceStrategy,
+    next: AtomicUsize,
+}
+
+impl Shared {
+    fn pick(&self) -> Option<Arc<Backend>> {
+        let backends = self.backends.read().unwrap();
+        let healthy: Vec<_> = backends
+            .iter()
+            .filter(|backend| backend.healthy.load(Ordering::Relaxed))
+            .collect();
+        let picked = match (self.strategy, healthy.len()) {
+            (_, 0) => return None,
+            (_, 1) => healthy[0],
+            (BalanceStrategy::RoundRobin, count) => {
+                healthy[self.next.fetch_add(1, Ordering::Relaxed) % count]
+            }
+            (BalanceStrategy::PowerOfTwoChoices, count) => {
+                let first = random_index(count);
+                let second = (first + 1 + random_index(count - 1)) % count;
+                let load = |index: usize| healthy[index].in_flight.load(Ordering::Relaxed);
+                match load(first) <= load(second) {
+                    true => healthy[first],
+                    false => healthy[second],
+                }
+            }
+        };
+        Some(picked.clone())
+    }
+}
+
+fn random_index(len: usize) -> usize {
+    ((random_fraction() * len as f64) as usize).min(len - 1)
+}
+
+struct HealthChecker {
+    resolver: Box<dyn Resolver>,
+    config: BalanceConfig,
+}
+
+impl HealthChecker {
+    async fn run(self, shared: Weak<Shared>) {
+        loop {
+            tokio::time::sleep(self.config.health_check_interval).await;
+            let Some(shared) = shared.upgrade() else {
+                return;
+            };
+            // a failed resolution keeps the previous endpoints
+            let _ = self.refresh(&shared).await;
+        }
+    }
+
+    async fn refresh(&self, shared: &Shared) -> Result<(), StdError> {
+        let uris = self.resolver.resolve().await?;
+        let backends: Vec<_> = {
+            let current = shared.backends.read().unwrap();
+            uris.into_iter()
+                .map(
+                    |uri| match current.iter().find(|backend| backend.uri == uri) {
+                        Some(backend) => backend.clone(),
+                        None => Arc::new(Backend::new(uri)),
+                    },
+                )
+                .collect()
+        };
+        join_all(backends.iter().map(|backend| async move {
+            let healthy = self.check(backend, shared.service).await;
+            backend.healthy.store(healthy, Ordering::Relaxed);
+        }))
+        .await;
+        *shared.backends.write().unwrap() = backends;
+        Ok(())
+    }
+
+    // endpoints that do not serve the requested version answer NOT_FOUND and are skipped like unhealthy ones
+    async fn check(&self, backend: &Backend, service: &str) -> bool {
+        let mut client = HealthClient::new(backend.channel.clone());
+        let request = HealthCheckRequest {
+            service: service.to_string(),
+        };
+        match tokio::time::timeout(self.config.health_check_timeout, client.check(request)).await {
+            Ok(Ok(response)) => response.into_inner().status() == ServingStatus::Serving,
+            _ => false,
+        }
+    }
+}
+
+/// A channel over every healthy endpoint serving one API version.
+///
+/// Endpoints are resolved and health checked when the channel is created and then every
+/// `health_check_interval` in the background, until the last clone of the channel is dropped.
+#[derive(Debug, Clone)]
+pub struct BalancedChannel {
+    shared: Arc<Shared>,
+}
+
+impl BalancedChannel {
+    pub async fn new(
+        resolver: impl Resolver,
+        service: &'static str,
+        config: BalanceConfig,
+    ) -> Result<Self, StdError> {
+        let shared = Arc::new(Shared {
+            backends: RwLock::new(Vec::new()),
+            service,
+            strategy: config.strategy,
+            next: AtomicUsize::new(0),
+        });
+        let checker = HealthChecker {
+            resolver: Box::new(resolver),
+            config,
+        };
+        checker.refresh(&shared).await?;
+        tokio::spawn(checker.run(Arc::downgrade(&shared)));
+        Ok(Self { shared })
+    }
+
+    /// The endpoints currently receiving calls.
+    pub fn healthy_endpoints(&self) -> Vec<Uri> {
+        self.shared
+            .backends
+            .read()
+            .unwrap()
+            .iter()
+            .filter(|backend| backend.healthy.load(Ordering::Relaxed))
+            .map(|backend| backend.uri.clone())
+            .collect()
+    }
+}
+
+impl Service<Request<BoxBody>> for BalancedChannel {
+    type Response = Response<Body>;
+    type Error = StdError;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    // readiness is checked on the picked endpoint, which is only known once the request is there
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, request: Request<BoxBody>) -> Self::Future {
+        let Some(backend) = self.shared.pick() else {
+            let status = Status::unavailable(format!(
+                "no healthy endpoint serves {}",
+                self.shared.service
+            ));
+            return Box::pin(async move { Err(status.into()) });
+        };
+        Box::pin(async move {
+            let mut channel = backend.channel.clone();
+            let in_flight = InFlight::start(&backend);
+            let result = async {
+                poll_fn(|cx| channel.poll_ready(cx)).await?;
+                channel.call(request).await
+            }
+            .await;
+            drop(in_flight);
+            // connection errors evict the endpoint until it passes a health check again
+            if result.is_err() {
+                backend.healthy.store(false, Ordering::Relaxed);
+            }
+            result.map_err(Into::into)
+        })
+    }
+}
@@ -0,0 +1,204 @@
+use std::time::Duration;
+
+use futures_util::future::join_all;
+use tokio::sync::{mpsc, oneshot};
+use tonic::codegen::{Body, Bytes, StdError};
+use tonic::{Code, Extensions, Response, Status};
+
+use crate::api::inner;
+use crate::capabilities::{Feature, Method};
+use crate::wrappers::vector_service_client::VectorServiceClient;
+
+#[derive(Debug, Clone)]
+pub struct BatchConfig {
+    // how long the first call of a batch waits for others to join it
+    pub window: Duration,
+    // a batch is sent as soon as it holds this many vectors, even within the window, and never holds
+    // more. keep it within the server's limit, a single call with more vectors is sent on its own
+    pub max_vectors: usize,
+}
+
+impl Default for BatchConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(5),
+            max_vectors: 100,
+        }
+    }
+}
+
+type Reply = oneshot::Sender<Result<Response<inner::SumResponse>, Status>>;
+
+struct Pending {
+    request: inner::SumRequest,
+    reply: Reply,
+}
+
+/// A versioned [`VectorServiceClient`] that coalesces concurrent `sum` calls into one multi-vector request.
+///
+/// Each caller gets back the sums of its own vectors, in order. A batch rejected as invalid is resent
+/// call by call, so each caller only sees the violations of its own request. Versions that can't carry more than one
+/// vector per request are called once per `sum` instead. Batched calls are plain messages,
+/// there is no per-call metadata to merge.
+#[derive(Debug, Clone)]
+pub struct BatchingClient<T> {
+    client: VectorServiceClient<T>,
+    // `None` when the client's version can't batch
+    queue: Option<mpsc::UnboundedSender<Pending>>,
+}
+
+impl<T> BatchingClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + Sync + 'static,
+    T::Future: Send,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    pub fn new(client: VectorServiceClient<T>, config: BatchConfig) -> Self {
+        let queue = client.supports(Method::Sum, Feature::MultiVector).then(|| {
+            let (queue, pending) = mpsc::unbounded_channel();
+            tokio::spawn(collect_batches(client.clone(), config, pending));
+            queue
+        });
+        Self { client, queue }
+    }
+
+    pub fn client(&self) -> &VectorServiceClient<T> {
+        &self.client
+    }
+
+    pub async fn sum(
+        &self,
+        request: inner::SumRequest,
+    ) -> Result<Response<inner::SumResponse>, Status> {
+        let Some(queue) = &self.queue else {
+            return self.client.clone().sum(request).await;
+        };
+        let (reply, response) = oneshot::channel();
+        queue
+            .send(Pending { request, reply })
+            .map_err(|_| Status::unavailable("the batching task stopped"))?;
+        response
+            .await
+            .map_err(|_| Status::internal("the batch was dropped before it was answered"))?
+    }
+}
+
+// runs until every clone of the batching client is dropped
+async fn collect_batches<T>(
+    client: VectorServiceClient<T>,
+    config: BatchConfig,
+    mut pending: mpsc::UnboundedReceiver<Pending>,
+) where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone + Send + Sync + 'static,
+    T::Future: Send,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    // a call that would have made the last batch too large starts the next one
+    let mut carried = None;
+    loop {
+        let first = match carried.take() {
+            Some(first) => first,
+            None => match pending.recv().await {
+                Some(first) => first,
+                None => break,
+            },
+        };
+        let mut vectors = first.request.vectors.len();
+        let mut batch = vec![first];
+        let window = tokio::time::sleep(config.window);
+        tokio::pin!(window);
+        while vectors < config.max_vectors {
+            tokio::select! {
+                next = pending.recv() => match next {
+                    Some(next) if vectors + next.request.vectors.len() > config.max_vectors => {
+                        carried = Some(next);
+                        break;
+                    }
+                    Some(next) => {
+                        vectors += next.request.vectors.len();
+                        batch.push(next);
+                    }
+                    None => break,
+                },
+                _ = &mut window => break,
+            }
+        }
+        tokio::spawn(send_batch(client.clone(), batch));
+    }
+}
+
+async fn send_batch<T>(mut client: VectorServiceClient<T>, batch: Vec<Pending>)
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    if batch.len() == 1 {
+        let Pending { request, reply } = batch.into_iter().next().unwrap();
+        let _ = reply.send(client.sum(request).await);
+        return;
+    }
+    let (requests, callers): (Vec<_>, Vec<_>) = batch
+        .into_iter()
+        .map(|Pending { request, reply }| {
+            let count = request.vectors.len();
+            (request, (count, reply))
+        })
+        .unzip();
+    let vectors: Vec<_> = requests
+        .iter()
+        .flat_map(|request| request.vectors.iter().cloned())
+        .collect();
+    let expected = vectors.len();
+
+    let (metadata, response, _) = match client.sum(inner::SumRequest { vectors }).await {
+        Ok(response) => response.into_parts(),
+        // one caller's violations fail the whole batch, with paths into the batch rather than
+        // that caller's request, so every caller is answered by a call of its own instead
+        Err(status) if status.code() == Code::InvalidArgument => {
+            let calls = requests
+                .into_iter()
+                .zip(callers)
+                .map(|(request, (_, reply))| {
+                    let mut client = client.clone();
+                    async move {
+                        let _ = reply.send(client.sum(request).await);
+                    }
+                });
+            join_all(calls).await;
+            return;
+        }
+        Err(status) => {
+            for (_, reply) in callers {
+                let _ = reply.send(Err(status.clone()));
+            }
+            return;
+        }
+    };
+    if response.sum.len() != expected {
+        let status = Status::internal(format!(
+            "a batch of {expected} vectors was answered with {} sums",
+            response.sum.len()
+        ));
+        for (_, reply) in callers {
+            let _ = reply.send(Err(status.clone()));
+        }
+        return;
+    }
+    let mut sums = response.sum.into_iter();
+    for (count, reply) in callers {
+        let response = inner::SumResponse {
+            sum: sums.by_ref().take(count).collect(),
+        };
+        let _ = reply.send(Ok(Response::from_parts(
+            metadata.clone(),
+            response,
+            Extensions::default(),
+        )));
+    }
+}
@@ -0,0 +1,58 @@
+use crate::wrappers::vector_service_client::SupportedVersion;
+
+/// The methods of the vector service, across all versions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Method {
+    Print,
+    Sum,
+}
+
+impl Method {
+    /// The method's name in the gRPC path, e.g. `Sum` in `/API.V1.VectorService/Sum`.
+    pub fn name(self) -> &'static str {
+        match self {
+            Method::Print => "Print",
+            Method::Sum => "Sum",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Self> {
+        [Method::Print, Method::Sum]
+            .into_iter()
+            .find(|method| method.name() == name)
+    }
+}
+
+/// Optional behavior a version may offer on top of a method.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Feature {
+    // a single request carries more than one vector
+    MultiVector,
+}
+
+/// What a single API version can do, declared next to the version in `api`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Capabilities {
+    pub methods: &'static [Method],
+    pub features: &'static [(Method, Feature)],
+}
+
+impl Capabilities {
+    pub fn has_method(&self, method: Method) -> bool {
+        self.methods.contains(&method)
+    }
+
+    pub fn supports(&self, method: Method, feature: Feature) -> bool {
+        self.has_method(method) && self.features.contains(&(method, feature))
+    }
+}
+
+// the version and method of a gRPC path such as `/API.V1.VectorService/Sum`,
+// `None` for calls to other services
+pub(crate) fn versioned_call(path: &str) -> Option<(SupportedVersion, Method)> {
+    let (service, method) = path.strip_prefix('/')?.split_once('/')?;
+    let version = SupportedVersion::all()
+        .iter()
+        .find(|version| version.service_name() == service)?;
+    Some((*version, Method::from_name(method)?))
+}
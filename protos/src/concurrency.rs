@@ -0,0 +1,359 @@
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio::sync::oneshot;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::codegen::{BoxFuture, Service};
+use tonic::server::NamedService;
+use tonic::Status;
+use tower_layer::Layer;
+
+use crate::capabilities::versioned_call;
+use crate::errors::VectorError;
+use crate::serving::take_ready;
+use crate::wrappers::vector_service_client::SupportedVersion;
+
+// `ErrorInfo.reason` of the calls shed because too many were waiting already
+pub const OVERLOADED_REASON: &str = "OVERLOADED";
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ConcurrencyLimit {
+    Fixed {
+        max_in_flight: usize,
+    },
+    // grows by one after a limit's worth of calls answered within `target_latency`,
+    // shrinks by a tenth on every slower one
+    Adaptive {
+        initial: usize,
+        min: usize,
+        max: usize,
+        target_latency: Duration,
+    },
+}
+
+/// A concurrency limit that could never let a call through, or starts outside its own range.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfigError(pub String);
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl ConcurrencyLimit {
+    // a limit that can reach 0 never lets another call through
+    fn check(self) -> Result<(), ConfigError> {
+        let error = |message: String| Err(ConfigError(message));
+        match self {
+            ConcurrencyLimit::Fixed { max_in_flight: 0 } => {
+                error("a fixed concurrency limit must let at least one call in".to_string())
+            }
+            ConcurrencyLimit::Adaptive { min: 0, .. } => {
+                error("an adaptive concurrency limit must let at least one call in".to_string())
+            }
+            ConcurrencyLimit::Adaptive {
+                initial, min, max, ..
+            } if !(min..=max).contains(&initial) => error(format!(
+                "an adaptive concurrency limit must start within {min}..={max}, not at {initial}"
+            )),
+            _ => Ok(()),
+        }
+    }
+
+    fn initial(self) -> usize {
+        match self {
+            ConcurrencyLimit::Fixed { max_in_flight } => max_in_flight,
+            ConcurrencyLimit::Adaptive { initial, .. } => initial,
+        }
+    }
+}
+
+/// How many calls run at once, and how many more may wait for a slot before new ones are shed.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct LimitConfig {
+    pub limit: ConcurrencyLimit,
+    pub max_queue: usize,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct LimiterStats {
+    pub limit: usize,
+    pub in_flight: usize,
+    pub queued: usize,
+    // calls rejected because the queue was full
+    pub shed: u64,
+}
+
+#[derive(Debug)]
+struct State {
+    limit: usize,
+    in_flight: usize,
+    // calls answered within the target latency since the adaptive limit last grew
+    fast: usize,
+    waiting: VecDeque<oneshot::Sender<Permit>>,
+    shed: u64,
+}
+
+#[derive(Debug)]
+struct Limiter {
+    name: &'static str,
+    config: LimitConfig,
+    state: Mutex<State>,
+}
+
+impl Limiter {
+    fn new(name: &'static str, config: LimitConfig) -> Result<Arc<Self>, ConfigError> {
+        config.limit.check()?;
+        Ok(Arc::new(Self {
+            name,
+            config,
+            state: Mutex::new(State {
+                limit: config.limit.initial(),
+                in_flight: 0,
+                fast: 0,
+                waiting: VecDeque::new(),
+                shed: 0,
+            }),
+        }))
+    }
+
+    async fn acquire(self: &Arc<Self>) -> Result<Permit, Status> {
+        let waiting = {
+            let mut state = self.state.lock().unwrap();
+            if state.in_flight < state.limit {
+                state.in_flight += 1;
+                return Ok(Permit::new(self.clone()));
+            }
+            // callers that gave up waiting still hold a place in the queue
+            state.waiting.retain(|waiter| !waiter.is_closed());
+            if state.waiting.len() >= self.config.max_queue {
+                state.shed += 1;
+                return Err(VectorError::Unavailable {
+                    reason: OVERLOADED_REASON.to_string(),
+                    message: format!(
+                        "{} is overloaded, {} calls are already waiting",
+                        self.name,
+                        state.waiting.len()
+                    ),
+                    retry_after: None,
+                }
+                .into());
+            }
+            let (waiter, waiting) = oneshot::channel();
+            state.waiting.push_back(waiter);
+            waiting
+        };
+        waiting
+            .await
+            .map_err(|_| Status::internal("the concurrency limiter dropped a waiting call"))
+    }
+
+    fn release(self: &Arc<Self>, latency: Duration) {
+        let mut state = self.state.lock().unwrap();
+        state.in_flight -= 1;
+        if let ConcurrencyLimit::Adaptive {
+            min,
+            max,
+            target_latency,
+            ..
+        } = self.config.limit
+        {
+            if latency > target_latency {
+                state.limit = ((state.limit as f64 * 0.9) as usize).max(min);
+                state.fast = 0;
+            } else {
+                state.fast += 1;
+                if state.fast >= state.limit {
+                    state.limit = (state.limit + 1).min(max);
+                    state.fast = 0;
+                }
+            }
+        }
+        // the freed slots go straight to the longest waiting calls
+        while state.in_flight < state.limit {
+            let Some(waiter) = state.waiting.pop_front() else {
+                break;
+            };
+            state.in_flight += 1;
+            if let Err(mut permit) = waiter.send(Permit::new(self.clone())) {
+                // the caller gave up, its slot goes to the next one
+                permit.limiter = None;
+                state.in_flight -= 1;
+            }
+        }
+    }
+
+    fn stats(&self) -> LimiterStats {
+        let state = self.state.lock().unwrap();
+        LimiterStats {
+            limit: state.limit,
+            in_flight: state.in_flight,
+            queued: state
+                .waiting
+                .iter()
+                .filter(|waiter| !waiter.is_closed())
+                .count(),
+            shed: state.shed,
+        }
+    }
+}
+
+// a slot of a limiter, freed on drop
+#[derive(Debug)]
+struct Permit {
+    // `None` once the slot was handed back without the permit being used
+    limiter: Option<Arc<Limiter>>,
+    acquired: Instant,
+}
+
+impl Permit {
+    fn new(limiter: Arc<Limiter>) -> Self {
+        Self {
+            limiter: Some(limiter),
+            acquired: Instant::now(),
+        }
+    }
+}
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        if let Some(limiter) = self.limiter.take() {
+            limiter.release(self.acquired.elapsed());
+        }
+    }
+}
+
+/// A global concurrency limit over every versioned call, and a limit of its own for each version.
+///
+/// Clones share their limiters, so the stats read from any clone are those of the running server.
+#[derive(Debug, Clone, Default)]
+pub struct ConcurrencyLimits {
+    global: Option<Arc<Limiter>>,
+    versions: Vec<(SupportedVersion, Arc<Limiter>)>,
+}
+
+impl ConcurrencyLimits {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Fails if the limit can drop to 0, or an adaptive one starts outside its range.
+    pub fn global(mut self, config: LimitConfig) -> Result<Self, ConfigError> {
+        self.global = Some(Limiter::new("the server", config)?);
+        Ok(self)
+    }
+
+    /// Fails like [`ConcurrencyLimits::global`].
+    pub fn version(
+        mut self,
+        version: SupportedVersion,
+        config: LimitConfig,
+    ) -> Result<Self, ConfigError> {
+        let limiter = Limiter::new(version.service_name(), config)?;
+        self.versions.retain(|(existing, _)| *existing != version);
+        self.versions.push((version, limiter));
+        Ok(self)
+    }
+
+    pub fn global_stats(&self) -> Option<LimiterStats> {
+        self.global.as_ref().map(|limiter| limiter.stats())
+    }
+
+    pub fn version_stats(&self, version: SupportedVersion) -> Option<LimiterStats> {
+        self.limiter(version.service_name())
+            .map(|limiter| limiter.stats())
+    }
+
+    /// The global limit, for the server's stack. Per-version limits are applied by
+    /// `add_limited_services_to_server`.
+    pub fn global_layer(&self) -> ConcurrencyLimitLayer {
+        ConcurrencyLimitLayer {
+            limiter: self.global.clone(),
+        }
+    }
+
+    // wraps a versioned service in the limiter of its version
+    pub(crate) fn limit_service<S: NamedService>(&self, service: S) -> ConcurrencyLimited<S> {
+        ConcurrencyLimited {
+            inner: service,
+            limiter: self.limiter(S::NAME),
+        }
+    }
+
+    fn limiter(&self, service: &str) -> Option<Arc<Limiter>> {
+        self.versions
+            .iter()
+            .find(|(version, _)| version.service_name() == service)
+            .map(|(_, limiter)| limiter.clone())
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimitLayer {
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl<S> Layer<S> for ConcurrencyLimitLayer {
+    type Service = ConcurrencyLimited<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConcurrencyLimited {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Holds versioned calls until their limiter has a slot for them, or sheds them with `UNAVAILABLE`
+/// when too many are waiting already.
+#[derive(Debug, Clone)]
+pub struct ConcurrencyLimited<S> {
+    inner: S,
+    // every call goes through when `None`
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl<S: NamedService> NamedService for ConcurrencyLimited<S> {
+    const NAME: &'static str = S::NAME;
+}
+
+impl<S, B> Service<Request<B>> for ConcurrencyLimited<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send + 'static,
+    B: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let limiter = match versioned_call(request.uri().path()) {
+            Some(_) => self.limiter.clone(),
+            None => None,
+        };
+        let Some(limiter) = limiter else {
+            return Box::pin(self.inner.call(request));
+        };
+        let mut inner = take_ready(&mut self.inner);
+        Box::pin(async move {
+            let _permit = match limiter.acquire().await {
+                Ok(permit) => permit,
+                Err(status) => return Ok(status.to_http()),
+            };
+            inner.call(request).await
+        })
+    }
+}
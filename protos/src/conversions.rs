@@ -0,0 +1,156 @@
+//! Conversions between API versions.
+//!
+//! Every version only describes how its messages step to the next version up the chain
+//! (`V1 -> V2 -> Inner`), via [`Upgrade`] and [`Downgrade`]. [`ToInner`] and [`FromInner`] compose
+//! those steps, so a version several steps away from `Inner` needs no conversion code of its own.
+//! A version with a missing step simply does not implement `ToInner`/`FromInner` for that message,
+//! which fails to compile wherever the adapters need it.
+//!
+//! Upgrades always succeed, a newer version can express everything an older one could. Downgrades may not,
+//! and every step declares with [`Downgrade::ROUND_TRIP`] whether a message survives an upgrade and a downgrade back.
+
+use std::fmt;
+
+use crate::api_versions::Inner;
+
+/// A marker type describing the message types of one API version.
+pub trait Version: Sized + Send + Sync + 'static {
+    type PrintRequest;
+    type PrintResponse;
+    type SumRequest;
+    type SumResponse;
+}
+
+/// A version that is followed by another one in the chain.
+pub trait Successor: Version {
+    type Next: Version;
+}
+
+/// Selects one message out of a [`Version`], e.g. `SumRequest::Of<V1>` is `api::v1::SumRequest`.
+pub trait MessageKind {
+    type Of<V: Version>;
+}
+
+pub mod kinds {
+    use super::{MessageKind, Version};
+
+    pub struct PrintRequest;
+    pub struct PrintResponse;
+    pub struct SumRequest;
+    pub struct SumResponse;
+
+    impl MessageKind for PrintRequest {
+        type Of<V: Version> = V::PrintRequest;
+    }
+    impl MessageKind for PrintResponse {
+        type Of<V: Version> = V::PrintResponse;
+    }
+    impl MessageKind for SumRequest {
+        type Of<V: Version> = V::SumRequest;
+    }
+    impl MessageKind for SumResponse {
+        type Of<V: Version> = V::SumResponse;
+    }
+}
+
+/// Converts a message of this version into the next version's counterpart.
+pub trait Upgrade<K: MessageKind>: Successor {
+    fn upgrade(message: K::Of<Self>) -> K::Of<Self::Next>;
+}
+
+/// Converts a message of the next version back into this version's counterpart.
+pub trait Downgrade<K: MessageKind>: Successor {
+    const ROUND_TRIP: RoundTrip;
+
+    fn downgrade(message: K::Of<Self::Next>) -> Result<K::Of<Self>, ConversionError>;
+}
+
+/// What downgrading an upgraded message gives back, checked by the property tests.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RoundTrip {
+    // the original message, always
+    Lossless,
+    // either the original message or a `ConversionError`, never a different message
+    Lossy(&'static str),
+}
+
+/// A message that has no counterpart in the version it is downgraded to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConversionError(pub String);
+
+impl fmt::Display for ConversionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ConversionError {}
+
+/// Upgrades a message all the way to the inner service's types.
+pub trait ToInner<K: MessageKind>: Version {
+    fn to_inner(message: K::Of<Self>) -> K::Of<Inner>;
+}
+
+/// Downgrades a message of the inner service all the way to this version.
+pub trait FromInner<K: MessageKind>: Version {
+    fn from_inner(message: K::Of<Inner>) -> Result<K::Of<Self>, ConversionError>;
+}
+
+impl<K: MessageKind> ToInner<K> for Inner {
+    fn to_inner(message: K::Of<Self>) -> K::Of<Inner> {
+        message
+    }
+}
+
+impl<K, V> ToInner<K> for V
+where
+    K: MessageKind,
+    V: Upgrade<K>,
+    V::Next: ToInner<K>,
+{
+    fn to_inner(message: K::Of<Self>) -> K::Of<Inner> {
+        V::Next::to_inner(V::upgrade(message))
+    }
+}
+
+impl<K: MessageKind> FromInner<K> for Inner {
+    fn from_inner(message: K::Of<Inner>) -> Result<K::Of<Self>, ConversionError> {
+        Ok(message)
+    }
+}
+
+impl<K, V> FromInner<K> for V
+where
+    K: MessageKind,
+    V: Downgrade<K>,
+    V::Next: FromInner<K>,
+{
+    fn from_inner(message: K::Of<Inner>) -> Result<K::Of<Self>, ConversionError> {
+        V::downgrade(V::Next::from_inner(message)?)
+    }
+}
+
+// for messages that did not change between a version and the next one
+macro_rules! unchanged_messages {
+    ($version:ty, $($kind:ident),*) => {
+        $(
+            impl $crate::conversions::Upgrade<$crate::conversions::kinds::$kind> for $version {
+                fn upgrade(
+                    message: <$version as $crate::conversions::Version>::$kind,
+                ) -> <<$version as $crate::conversions::Successor>::Next as $crate::conversions::Version>::$kind {
+                    message
+                }
+            }
+            impl $crate::conversions::Downgrade<$crate::conversions::kinds::$kind> for $version {
+                const ROUND_TRIP: $crate::conversions::RoundTrip = $crate::conversions::RoundTrip::Lossless;
+
+                fn downgrade(
+                    message: <<$version as $crate::conversions::Successor>::Next as $crate::conversions::Version>::$kind,
+                ) -> Result<<$version as $crate::conversions::Version>::$kind, $crate::conversions::ConversionError> {
+                    Ok(message)
+                }
+            }
+        )*
+    };
+}
+pub(crate) use unchanged_messages;
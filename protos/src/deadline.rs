@@ -0,0 +1,166 @@
+use std::future::Future;
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tokio_util::sync::DropGuard;
+use tonic::codegen::http;
+use tonic::codegen::Service;
+use tonic::{Request, Status};
+use tower_layer::Layer;
+
+pub use tokio_util::sync::CancellationToken;
+
+// how long the caller waits for an answer, e.g. `100m` for 100 milliseconds
+pub const GRPC_TIMEOUT_HEADER: &str = "grpc-timeout";
+
+/// When a call has to be answered by, from the caller's `grpc-timeout`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Deadline(Instant);
+
+impl Deadline {
+    pub fn at(instant: Instant) -> Self {
+        Self(instant)
+    }
+
+    pub fn instant(self) -> Instant {
+        self.0
+    }
+
+    pub fn remaining(self) -> Duration {
+        self.0.saturating_duration_since(Instant::now())
+    }
+
+    pub fn is_expired(self) -> bool {
+        self.remaining().is_zero()
+    }
+
+    // a `grpc-timeout` counted from `start`, `None` if it isn't one
+    fn from_timeout(timeout: &str, start: Instant) -> Option<Self> {
+        if timeout.is_empty() || !timeout.is_ascii() {
+            return None;
+        }
+        let (value, unit) = timeout.split_at(timeout.len() - 1);
+        // at most 8 digits, which also keeps the hours from overflowing
+        if value.len() > 8 {
+            return None;
+        }
+        let value: u64 = value.parse().ok()?;
+        let timeout = match unit {
+            "H" => Duration::from_secs(value * 60 * 60),
+            "M" => Duration::from_secs(value * 60),
+            "S" => Duration::from_secs(value),
+            "m" => Duration::from_millis(value),
+            "u" => Duration::from_micros(value),
+            "n" => Duration::from_nanos(value),
+            _ => return None,
+        };
+        start.checked_add(timeout).map(Self)
+    }
+}
+
+/// The deadline of a call, or `None` if its caller didn't set one.
+pub fn deadline<T>(request: &Request<T>) -> Option<Deadline> {
+    request.extensions().get::<Deadline>().copied()
+}
+
+/// A token cancelled once the call is over, answered or abandoned by its caller. Work that outlives
+/// the call's future, on spawned tasks or blocking threads, should stop when it is.
+///
+/// `None` if the request did not pass through a version adapter.
+pub fn cancellation<T>(request: &Request<T>) -> Option<CancellationToken> {
+    request.extensions().get::<CancellationToken>().cloned()
+}
+
+/// Stamps calls with their deadline as they arrive, so the time they spend queued behind the other
+/// layers counts against it. Without it, the version adapters count from when the call reaches them.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct DeadlineLayer;
+
+impl<S> Layer<S> for DeadlineLayer {
+    type Service = DeadlineService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeadlineService { inner }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct DeadlineService<S> {
+    inner: S,
+}
+
+impl<S, B> Service<http::Request<B>> for DeadlineService<S>
+where
+    S: Service<http::Request<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut request: http::Request<B>) -> Self::Future {
+        let deadline = request
+            .headers()
+            .get(GRPC_TIMEOUT_HEADER)
+            .and_then(|timeout| timeout.to_str().ok())
+            .and_then(|timeout| Deadline::from_timeout(timeout, Instant::now()));
+        if let Some(deadline) = deadline {
+            request.extensions_mut().insert(deadline);
+        }
+        self.inner.call(request)
+    }
+}
+
+// what the version adapters hand the inner service with every call: its deadline, and a token
+// cancelled when the scope is dropped, once the call is answered or its future is dropped
+pub(crate) struct CallScope {
+    deadline: Option<Deadline>,
+    _cancel_on_drop: DropGuard,
+}
+
+impl CallScope {
+    pub(crate) fn enter<T>(request: &mut Request<T>) -> Self {
+        let deadline = deadline(request).or_else(|| {
+            request
+                .metadata()
+                .get(GRPC_TIMEOUT_HEADER)
+                .and_then(|timeout| timeout.to_str().ok())
+                .and_then(|timeout| Deadline::from_timeout(timeout, Instant::now()))
+        });
+        let token = CancellationToken::new();
+        if let Some(deadline) = deadline {
+            request.extensions_mut().insert(deadline);
+        }
+        request.extensions_mut().insert(token.clone());
+        Self {
+            deadline,
+            _cancel_on_drop: token.drop_guard(),
+        }
+    }
+
+    // runs the call until its deadline, calls that are out of time already never start
+    pub(crate) async fn run<T>(
+        self,
+        call: impl Future<Output = Result<T, Status>>,
+    ) -> Result<T, Status> {
+        let Some(deadline) = self.deadline else {
+            return call.await;
+        };
+        if deadline.is_expired() {
+            return Err(Status::deadline_exceeded(
+                "the deadline passed before the call started",
+            ));
+        }
+        let deadline = tokio::time::Instant::from_std(deadline.instant());
+        tokio::time::timeout_at(deadline, call)
+            .await
+            .unwrap_or_else(|_| {
+                Err(Status::deadline_exceeded(
+                    "the deadline passed before the call was answered",
+                ))
+            })
+    }
+}
@@ -0,0 +1,242 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::time::Duration;
+
+use tonic::{Code, Status};
+use tonic_types::{ErrorDetails, StatusExt};
+
+pub use tonic_types::{FieldViolation, PreconditionViolation};
+
+// `ErrorInfo.domain` of the errors the vector services raise
+pub const ERROR_DOMAIN: &str = "vectors.api";
+
+/// The errors of the vector services, sent as a `google.rpc.Status` with details.
+///
+/// Inner services return them with `?`, versioned clients decode them back with `try_sum` and `try_print`.
+#[derive(Debug, Clone)]
+pub enum VectorError {
+    // with a google.rpc.BadRequest
+    InvalidArgument {
+        message: String,
+        violations: Vec<FieldViolation>,
+    },
+    // with a google.rpc.PreconditionFailure
+    FailedPrecondition {
+        message: String,
+        violations: Vec<PreconditionViolation>,
+    },
+    // `reason` goes in a google.rpc.ErrorInfo, `retry_after` in a google.rpc.RetryInfo
+    ResourceExhausted {
+        reason: String,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    Unavailable {
+        reason: String,
+        message: String,
+        retry_after: Option<Duration>,
+    },
+    // any other status, without details
+    Other {
+        code: Code,
+        message: String,
+    },
+}
+
+impl VectorError {
+    // the message only counts the violations, their field paths differ between versions
+    pub fn invalid_argument(violations: Vec<FieldViolation>) -> Self {
+        let message = match violations.len() {
+            1 => "invalid request: 1 field violation".to_string(),
+            count => format!("invalid request: {count} field violations"),
+        };
+        VectorError::InvalidArgument {
+            message,
+            violations,
+        }
+    }
+
+    pub fn code(&self) -> Code {
+        match self {
+            VectorError::InvalidArgument { .. } => Code::InvalidArgument,
+            VectorError::FailedPrecondition { .. } => Code::FailedPrecondition,
+            VectorError::ResourceExhausted { .. } => Code::ResourceExhausted,
+            VectorError::Unavailable { .. } => Code::Unavailable,
+            VectorError::Other { code, .. } => *code,
+        }
+    }
+
+    pub fn message(&self) -> &str {
+        match self {
+            VectorError::InvalidArgument { message, .. }
+            | VectorError::FailedPrecondition { message, .. }
+            | VectorError::ResourceExhausted { message, .. }
+            | VectorError::Unavailable { message, .. }
+            | VectorError::Other { message, .. } => message,
+        }
+    }
+
+    /// How long the server asked the caller to wait before trying again, if it did.
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            VectorError::ResourceExhausted { retry_after, .. }
+            | VectorError::Unavailable { retry_after, .. } => *retry_after,
+            _ => None,
+        }
+    }
+
+    /// Decodes the details of a status, those it doesn't carry are left empty.
+    pub fn from_status(status: &Status) -> Self {
+        let details = status.get_error_details();
+        let message = status.message().to_string();
+        let reason = details
+            .error_info()
+            .map(|info| info.reason.clone())
+            .unwrap_or_default();
+        let retry_after = details.retry_info().and_then(|info| info.retry_delay);
+        match status.code() {
+            Code::InvalidArgument => VectorError::InvalidArgument {
+                message,
+                violations: details
+                    .bad_request()
+                    .map(|bad_request| bad_request.field_violations.clone())
+                    .unwrap_or_default(),
+            },
+            Code::FailedPrecondition => VectorError::FailedPrecondition {
+                message,
+                violations: details
+                    .precondition_failure()
+                    .map(|failure| failure.violations.clone())
+                    .unwrap_or_default(),
+            },
+            Code::ResourceExhausted => VectorError::ResourceExhausted {
+                reason,
+                message,
+                retry_after,
+            },
+            Code::Unavailable => VectorError::Unavailable {
+                reason,
+                message,
+                retry_after,
+            },
+            code => VectorError::Other { code, message },
+        }
+    }
+}
+
+impl fmt::Display for VectorError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.code(), self.message())
+    }
+}
+
+impl std::error::Error for VectorError {}
+
+impl From<VectorError> for Status {
+    fn from(error: VectorError) -> Self {
+        let mut details = ErrorDetails::new();
+        match &error {
+            VectorError::InvalidArgument { violations, .. } => {
+                details.set_bad_request(violations.clone());
+            }
+            VectorError::FailedPrecondition { violations, .. } => {
+                details.set_precondition_failure(violations.clone());
+            }
+            VectorError::ResourceExhausted {
+                reason,
+                retry_after,
+                ..
+            }
+            | VectorError::Unavailable {
+                reason,
+                retry_after,
+                ..
+            } => {
+                details.set_error_info(reason.clone(), ERROR_DOMAIN, HashMap::new());
+                if retry_after.is_some() {
+                    details.set_retry_info(*retry_after);
+                }
+            }
+            VectorError::Other { .. } => {}
+        }
+        Status::with_error_details(error.code(), error.message(), details)
+    }
+}
+
+impl From<Status> for VectorError {
+    fn from(status: Status) -> Self {
+        Self::from_status(&status)
+    }
+}
+
+/// Where a version's sum request keeps its vectors, e.g. V1's single `vector` or V2's repeated `vectors`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SumFields {
+    pub vectors: &'static str,
+    // whether the field is a list, whose items are indexed in field paths
+    pub repeated: bool,
+}
+
+impl SumFields {
+    // the inner service speaks V2
+    pub(crate) const INNER: SumFields = SumFields {
+        vectors: "vectors",
+        repeated: true,
+    };
+
+    pub(crate) fn vector(self, index: usize) -> String {
+        match self.repeated {
+            true => format!("{}[{index}]", self.vectors),
+            false => self.vectors.to_string(),
+        }
+    }
+
+    /// Translates a field path of the inner sum request, e.g. `vectors[2].values` is V1's `vector.values`.
+    pub fn from_inner(self, path: &str) -> String {
+        let Some(rest) = path.strip_prefix(Self::INNER.vectors) else {
+            return path.to_string();
+        };
+        let (vectors, rest) = match rest.strip_prefix('[').and_then(|rest| rest.split_once(']')) {
+            Some((index, rest)) => match index.parse() {
+                Ok(index) => (self.vector(index), rest),
+                Err(_) => return path.to_string(),
+            },
+            None => (self.vectors.to_string(), rest),
+        };
+        // another field that only starts the same
+        if !rest.is_empty() && !rest.starts_with('.') {
+            return path.to_string();
+        }
+        format!("{vectors}{rest}")
+    }
+
+    // a field path of a single-vector call fanned out of a sum, as one of that sum's `vectors[index]`
+    pub(crate) fn to_request(self, path: &str, index: usize) -> String {
+        match path.strip_prefix(&self.vector(0)) {
+            Some(rest) if rest.is_empty() || rest.starts_with('.') => {
+                format!("{}{rest}", Self::INNER.vector(index))
+            }
+            _ => path.to_string(),
+        }
+    }
+}
+
+/// Rewrites the field paths of a status' `BadRequest`, keeping its other standard details and its metadata.
+pub fn rewrite_field_paths(status: Status, rewrite: impl Fn(&str) -> String) -> Status {
+    let mut details = status.get_error_details();
+    let Some(bad_request) = details.bad_request() else {
+        return status;
+    };
+    let violations: Vec<_> = bad_request
+        .field_violations
+        .iter()
+        .map(|violation| FieldViolation::new(rewrite(&violation.field), &violation.description))
+        .collect();
+    details.set_bad_request(violations);
+    Status::with_error_details_and_metadata(
+        status.code(),
+        status.message(),
+        details,
+        status.metadata().clone(),
+    )
+}
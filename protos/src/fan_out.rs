@@ -0,0 +1,129 @@
+use std::fmt;
+use std::future::Future;
+
+use futures_util::stream::{self, StreamExt};
+use tonic::codegen::Bytes;
+use tonic::metadata::MetadataMap;
+use tonic::{Extensions, Request, Response, Status};
+
+use crate::api::inner::{SumRequest, SumResponse};
+use crate::errors::{rewrite_field_paths, SumFields};
+
+// how many single-vector calls a fanned-out sum keeps in flight by default
+pub const DEFAULT_FAN_OUT_CONCURRENCY: usize = 8;
+
+/// Returned when some of the single-vector calls of a fanned-out sum failed.
+///
+/// `results` holds one entry per vector of the original request, in request order,
+/// so callers can keep the sums that did succeed.
+#[derive(Debug)]
+pub struct FanOutError {
+    pub results: Vec<Result<f32, Status>>,
+}
+
+impl FanOutError {
+    pub fn failures(&self) -> impl Iterator<Item = (usize, &Status)> {
+        self.results
+            .iter()
+            .enumerate()
+            .filter_map(|(index, result)| result.as_ref().err().map(|status| (index, status)))
+    }
+
+    pub fn partial_sums(&self) -> Vec<Option<f32>> {
+        self.results
+            .iter()
+            .map(|result| result.as_ref().ok().copied())
+            .collect()
+    }
+}
+
+impl fmt::Display for FanOutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failed = self.failures().count();
+        let total = self.results.len();
+        write!(f, "{failed} of {total} fanned-out sum calls failed")?;
+        if let Some((index, status)) = self.failures().next() {
+            write!(f, ", vectors[{index}]: {}", status.message())?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for FanOutError {}
+
+// the first failed call's status, with its details and metadata, for the message of the whole sum
+impl From<FanOutError> for Status {
+    fn from(error: FanOutError) -> Self {
+        let message = error.to_string();
+        match error.results.into_iter().find_map(Result::err) {
+            Some(status) => Status::with_details_and_metadata(
+                status.code(),
+                message,
+                Bytes::copy_from_slice(status.details()),
+                status.metadata().clone(),
+            ),
+            None => Status::unknown(message),
+        }
+    }
+}
+
+// splits a multi-vector sum into single-vector calls for versions that can only carry one vector,
+// and merges the results back into a single inner response. `fields` is where the calls' version
+// keeps its vector, failed calls name `vectors[i]` of the original request instead
+pub(crate) async fn fan_out_sum<F, Fut>(
+    request: Request<SumRequest>,
+    concurrency: usize,
+    fields: SumFields,
+    call: F,
+) -> Result<Response<SumResponse>, FanOutError>
+where
+    F: Fn(Request<SumRequest>) -> Fut,
+    Fut: Future<Output = Result<Response<SumResponse>, Status>>,
+{
+    let (metadata, _, request) = request.into_parts();
+    let calls = request.vectors.into_iter().map(|vector| {
+        let request = SumRequest {
+            vectors: vec![vector],
+        };
+        call(Request::from_parts(
+            metadata.clone(),
+            Extensions::default(),
+            request,
+        ))
+    });
+    let responses: Vec<_> = stream::iter(calls)
+        .buffered(concurrency.max(1))
+        .collect()
+        .await;
+
+    let mut response_metadata: Option<MetadataMap> = None;
+    let results: Vec<_> = responses
+        .into_iter()
+        .enumerate()
+        .map(|(index, result)| {
+            let (metadata, response, _) = result
+                .map_err(|status| {
+                    rewrite_field_paths(status, |field| fields.to_request(field, index))
+                })?
+                .into_parts();
+            response_metadata.get_or_insert(metadata);
+            match response.sum[..] {
+                [sum] => Ok(sum),
+                _ => Err(Status::internal(format!(
+                    "expected a single sum per fanned-out call, got {}",
+                    response.sum.len()
+                ))),
+            }
+        })
+        .collect();
+
+    if results.iter().any(Result::is_err) {
+        return Err(FanOutError { results });
+    }
+    let sum = results.into_iter().flatten().collect();
+    Ok(Response::from_parts(
+        response_metadata.unwrap_or_default(),
+        SumResponse { sum },
+        Extensions::default(),
+    ))
+}
@@ -1,19 +1,102 @@
+// tonic::Status is the error type of every generated service, boxing it would only add noise
+#![allow(clippy::result_large_err)]
+
+mod api_version;
+pub mod auth;
+mod balance;
+mod batch;
+mod capabilities;
+pub mod concurrency;
+pub mod conversions;
+pub mod deadline;
+pub mod errors;
+mod fan_out;
+mod overrides;
+mod policy;
+pub mod rate_limit;
+pub mod recording;
+pub mod serving;
+mod shadow;
+pub mod simulator;
+#[cfg(feature = "testing")]
+pub mod testing;
+pub mod validation;
 mod wrappers;
 
-mod api {
-    pub(crate) mod v1 {
+// the per-version types are public so version-specific code (e.g. overrides) can name them,
+// the inner types are exported through `vector_service`
+pub mod api {
+    pub mod v1 {
+        use crate::capabilities::Method;
+
         pub const VERSION_NAME: &str = "V1";
+        pub const SERVICE_NAME: &str = "API.V1.VectorService";
+        pub const API_VERSION: crate::api_version::ApiVersion =
+            crate::api_version::ApiVersion::new(VERSION_NAME, 1, true);
+        pub const CAPABILITIES: crate::capabilities::Capabilities =
+            crate::capabilities::Capabilities {
+                methods: &[Method::Print, Method::Sum],
+                features: &[],
+            };
+        pub const SUM_FIELDS: crate::errors::SumFields = crate::errors::SumFields {
+            vectors: "vector",
+            repeated: false,
+        };
+        pub type Overrides =
+            crate::overrides::MethodOverrides<PrintRequest, PrintResponse, SumRequest, SumResponse>;
         include!("api.v1.rs");
+
+        impl crate::conversions::Version for crate::api_versions::V1 {
+            type PrintRequest = PrintRequest;
+            type PrintResponse = PrintResponse;
+            type SumRequest = SumRequest;
+            type SumResponse = SumResponse;
+        }
     }
 
-    pub(crate) mod v2 {
+    pub mod v2 {
+        use crate::capabilities::{Feature, Method};
+
         pub const VERSION_NAME: &str = "V2";
+        pub const SERVICE_NAME: &str = "API.V2.VectorService";
+        pub const API_VERSION: crate::api_version::ApiVersion =
+            crate::api_version::ApiVersion::new(VERSION_NAME, 2, false);
+        pub const CAPABILITIES: crate::capabilities::Capabilities =
+            crate::capabilities::Capabilities {
+                methods: &[Method::Print, Method::Sum],
+                features: &[(Method::Sum, Feature::MultiVector)],
+            };
+        pub const SUM_FIELDS: crate::errors::SumFields = crate::errors::SumFields {
+            vectors: "vectors",
+            repeated: true,
+        };
+        pub type Overrides =
+            crate::overrides::MethodOverrides<PrintRequest, PrintResponse, SumRequest, SumResponse>;
         pub use super::v1::*;
         include!("api.v2.rs");
 
-        impl From<super::v1::SumRequest> for SumRequest {
-            fn from(value: super::v1::SumRequest) -> Self {
-                match value.vector {
+        use crate::api_versions::{V1, V2};
+        use crate::conversions::{
+            kinds, unchanged_messages, ConversionError, Downgrade, RoundTrip, Successor, Upgrade,
+            Version,
+        };
+
+        impl Version for V2 {
+            type PrintRequest = PrintRequest;
+            type PrintResponse = PrintResponse;
+            type SumRequest = SumRequest;
+            type SumResponse = SumResponse;
+        }
+
+        // V1 -> V2
+        impl Successor for V1 {
+            type Next = V2;
+        }
+        unchanged_messages!(V1, PrintRequest, PrintResponse);
+
+        impl Upgrade<kinds::SumRequest> for V1 {
+            fn upgrade(message: super::v1::SumRequest) -> SumRequest {
+                match message.vector {
                     Some(vector) => SumRequest {
                         vectors: vec![vector],
                     },
@@ -21,30 +104,55 @@ mod api {
                 }
             }
         }
-        impl From<SumRequest> for super::v1::SumRequest {
-            fn from(value: SumRequest) -> Self {
-                assert_eq!(value.vectors.len(), 1);
-                super::v1::SumRequest {
-                    vector: Some(value.vectors[0].clone()),
+        impl Downgrade<kinds::SumRequest> for V1 {
+            const ROUND_TRIP: RoundTrip =
+                RoundTrip::Lossy("a request without a vector upgrades to no vectors, V1 needs exactly one");
+
+            fn downgrade(message: SumRequest) -> Result<super::v1::SumRequest, ConversionError> {
+                match <[_; 1]>::try_from(message.vectors) {
+                    Ok([vector]) => Ok(super::v1::SumRequest {
+                        vector: Some(vector),
+                    }),
+                    Err(vectors) => Err(ConversionError(format!(
+                        "V1 sums exactly one vector per request, not {}",
+                        vectors.len()
+                    ))),
                 }
             }
         }
 
-        impl From<super::v1::SumResponse> for SumResponse {
-            fn from(value: super::v1::SumResponse) -> Self {
+        impl Upgrade<kinds::SumResponse> for V1 {
+            fn upgrade(message: super::v1::SumResponse) -> SumResponse {
                 SumResponse {
-                    sum: vec![value.sum],
+                    sum: vec![message.sum],
                 }
             }
         }
-        impl From<SumResponse> for super::v1::SumResponse {
-            fn from(value: SumResponse) -> Self {
-                assert_eq!(value.sum.len(), 1);
-                super::v1::SumResponse { sum: value.sum[0] }
+        impl Downgrade<kinds::SumResponse> for V1 {
+            const ROUND_TRIP: RoundTrip = RoundTrip::Lossless;
+
+            fn downgrade(message: SumResponse) -> Result<super::v1::SumResponse, ConversionError> {
+                match message.sum.as_slice() {
+                    &[sum] => Ok(super::v1::SumResponse { sum }),
+                    sums => Err(ConversionError(format!(
+                        "V1 answers exactly one sum per request, not {}",
+                        sums.len()
+                    ))),
+                }
             }
         }
     }
 
+    // the simulator's runtime controls, see `simulator`
+    pub mod admin {
+        include!("api.admin.rs");
+    }
+
+    // the file format of `recording`
+    pub mod recording {
+        include!("api.recording.rs");
+    }
+
     pub(crate) mod inner {
         pub use super::v1::Vector;
         pub use super::v2::{PrintRequest, PrintResponse, SumRequest, SumResponse};
@@ -52,12 +160,49 @@ mod api {
         include!("api.inner.rs");
 
         pub use vector_service_server::VectorService;
+
+        use crate::api_versions::{Inner, V2};
+        use crate::conversions::{unchanged_messages, Successor, Version};
+
+        impl Version for Inner {
+            type PrintRequest = PrintRequest;
+            type PrintResponse = PrintResponse;
+            type SumRequest = SumRequest;
+            type SumResponse = SumResponse;
+        }
+
+        // V2 -> Inner, the inner service currently speaks V2
+        impl Successor for V2 {
+            type Next = Inner;
+        }
+        unchanged_messages!(V2, PrintRequest, PrintResponse, SumRequest, SumResponse);
     }
 }
 
+// type-level markers for every version, used to compose conversions between them
+pub mod api_versions {
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct V1;
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct V2;
+    #[derive(Debug, Clone, Copy, Default)]
+    pub struct Inner;
+}
+
+// the descriptor sets build.rs writes for every version, for reflection-based tooling
+pub mod descriptors {
+    pub const V1: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/api.V1.bin"));
+    pub const V2: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/api.V2.bin"));
+    pub const INNER: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/api.inner.bin"));
+    pub const ALL: [&[u8]; 3] = [V1, V2, INNER];
+}
+
 pub mod vector_service {
     pub use crate::api::inner::*;
-    pub use crate::wrappers::{vector_service_client, vector_service_server};
+    pub use crate::api_version::{api_version, ApiVersion, API_VERSION_HEADER};
+    pub use crate::wrappers::{
+        typed_vector_service_client, vector_service_client, vector_service_server,
+    };
 }
 
 // these clients are what the client will actually use, and intended for showcasing.
@@ -0,0 +1,113 @@
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tonic::{async_trait, Request, Response, Status};
+
+pub(crate) type BoxFuture<'a, T> = Pin<Box<dyn Future<Output = T> + Send + 'a>>;
+type DefaultCall<'a, Req, Resp> =
+    Box<dyn FnOnce(Request<Req>) -> BoxFuture<'a, Result<Response<Resp>, Status>> + Send + 'a>;
+
+/// The default convert-call-convert path of a versioned method.
+///
+/// An override that only wants to run *around* the inner `VectorService` calls [`Next::run`];
+/// one that replaces it simply drops `next`.
+pub struct Next<'a, Req, Resp> {
+    call: DefaultCall<'a, Req, Resp>,
+}
+
+impl<'a, Req, Resp> Next<'a, Req, Resp> {
+    pub(crate) fn new<F>(call: F) -> Self
+    where
+        F: FnOnce(Request<Req>) -> BoxFuture<'a, Result<Response<Resp>, Status>> + Send + 'a,
+    {
+        Self {
+            call: Box::new(call),
+        }
+    }
+
+    pub async fn run(self, request: Request<Req>) -> Result<Response<Resp>, Status> {
+        (self.call)(request).await
+    }
+}
+
+/// A handler that takes over a single method of a single API version.
+///
+/// The request and response types are the version's own (e.g. `api::v1::SumRequest`),
+/// so an override can keep semantics the inner service no longer implements.
+#[async_trait]
+pub trait MethodOverride<Req, Resp>: Send + Sync + 'static
+where
+    Req: Send + 'static,
+    Resp: Send + 'static,
+{
+    async fn call(
+        &self,
+        request: Request<Req>,
+        next: Next<'_, Req, Resp>,
+    ) -> Result<Response<Resp>, Status>;
+}
+
+/// The overrides registered for one API version, one slot per method.
+///
+/// Each version module exposes this as `Overrides` with its own request/response types.
+pub struct MethodOverrides<PrintReq, PrintResp, SumReq, SumResp>
+where
+    PrintReq: Send + 'static,
+    PrintResp: Send + 'static,
+    SumReq: Send + 'static,
+    SumResp: Send + 'static,
+{
+    pub(crate) print: Option<Arc<dyn MethodOverride<PrintReq, PrintResp>>>,
+    pub(crate) sum: Option<Arc<dyn MethodOverride<SumReq, SumResp>>>,
+}
+
+impl<PrintReq, PrintResp, SumReq, SumResp> MethodOverrides<PrintReq, PrintResp, SumReq, SumResp>
+where
+    PrintReq: Send + 'static,
+    PrintResp: Send + 'static,
+    SumReq: Send + 'static,
+    SumResp: Send + 'static,
+{
+    pub fn print(&mut self, handler: impl MethodOverride<PrintReq, PrintResp>) -> &mut Self {
+        self.print = Some(Arc::new(handler));
+        self
+    }
+
+    pub fn sum(&mut self, handler: impl MethodOverride<SumReq, SumResp>) -> &mut Self {
+        self.sum = Some(Arc::new(handler));
+        self
+    }
+}
+
+impl<PrintReq, PrintResp, SumReq, SumResp> Default
+    for MethodOverrides<PrintReq, PrintResp, SumReq, SumResp>
+where
+    PrintReq: Send + 'static,
+    PrintResp: Send + 'static,
+    SumReq: Send + 'static,
+    SumResp: Send + 'static,
+{
+    fn default() -> Self {
+        Self {
+            print: None,
+            sum: None,
+        }
+    }
+}
+
+impl<PrintReq, PrintResp, SumReq, SumResp> Clone
+    for MethodOverrides<PrintReq, PrintResp, SumReq, SumResp>
+where
+    PrintReq: Send + 'static,
+    PrintResp: Send + 'static,
+    SumReq: Send + 'static,
+    SumResp: Send + 'static,
+{
+    fn clone(&self) -> Self {
+        Self {
+            print: self.print.clone(),
+            sum: self.sum.clone(),
+        }
+    }
+}
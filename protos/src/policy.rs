@@ -0,0 +1,350 @@
+use std::collections::hash_map::RandomState;
+use std::collections::HashMap;
+use std::future::Future;
+use std::hash::{BuildHasher, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use futures_util::stream::{FuturesUnordered, StreamExt};
+use tokio::time::{sleep, Instant};
+use tonic::codegen::{Body, Bytes, StdError};
+use tonic::{Code, Request, Response, Status};
+
+use crate::api::inner;
+use crate::capabilities::Method;
+use crate::wrappers::vector_service_client::VectorServiceClient;
+
+/// How the [`PolicyClient`] treats every call: deadlines, retries and hedging.
+///
+/// A method is either retried or hedged, never both; hedging wins when both are configured.
+#[derive(Debug, Clone, Default)]
+pub struct CallPolicy {
+    // deadline of the whole call, retries included, sent to the server as `grpc-timeout`
+    pub timeouts: HashMap<Method, Duration>,
+    pub retry: Option<RetryPolicy>,
+    pub hedging: Option<HedgingPolicy>,
+}
+
+impl CallPolicy {
+    #[must_use]
+    pub fn timeout(mut self, method: Method, timeout: Duration) -> Self {
+        self.timeouts.insert(method, timeout);
+        self
+    }
+
+    #[must_use]
+    pub fn retry(mut self, retry: RetryPolicy) -> Self {
+        self.retry = Some(retry);
+        self
+    }
+
+    #[must_use]
+    pub fn hedging(mut self, hedging: HedgingPolicy) -> Self {
+        self.hedging = Some(hedging);
+        self
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    // the first attempt included
+    pub max_attempts: u32,
+    pub initial_backoff: Duration,
+    pub max_backoff: Duration,
+    pub multiplier: f64,
+    // fraction of every backoff that is randomized, between 0 and 1
+    pub jitter: f64,
+    pub retryable_codes: Vec<Code>,
+    pub budget: RetryBudget,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            initial_backoff: Duration::from_millis(50),
+            max_backoff: Duration::from_secs(2),
+            multiplier: 2.0,
+            jitter: 0.2,
+            retryable_codes: vec![Code::Unavailable, Code::ResourceExhausted],
+            budget: RetryBudget::default(),
+        }
+    }
+}
+
+impl RetryPolicy {
+    fn backoff(&self, retry: u32) -> Duration {
+        let backoff = self.initial_backoff.as_secs_f64() * self.multiplier.powi(retry as i32);
+        let backoff = backoff.min(self.max_backoff.as_secs_f64());
+        let jitter = self.jitter.clamp(0.0, 1.0) * random_fraction();
+        Duration::from_secs_f64(backoff * (1.0 - jitter))
+    }
+}
+
+/// Caps retries across all calls of a client, following gRPC's retry throttling:
+/// every failure costs a token, every success gives back `token_ratio`,
+/// and retries stop while fewer than half of `max_tokens` are left.
+#[derive(Debug, Clone)]
+pub struct RetryBudget {
+    pub max_tokens: f64,
+    pub token_ratio: f64,
+}
+
+impl Default for RetryBudget {
+    fn default() -> Self {
+        Self {
+            max_tokens: 10.0,
+            token_ratio: 0.1,
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct HedgingPolicy {
+    // the first attempt included
+    pub max_attempts: u32,
+    pub delay: Duration,
+    // only idempotent methods should be hedged
+    pub methods: Vec<Method>,
+    // any other code is the call's answer, no more attempts are launched after it
+    pub retryable_codes: Vec<Code>,
+}
+
+impl Default for HedgingPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 2,
+            delay: Duration::from_millis(100),
+            methods: vec![Method::Print, Method::Sum],
+            retryable_codes: vec![Code::Unavailable, Code::ResourceExhausted],
+        }
+    }
+}
+
+#[derive(Debug)]
+struct BudgetState {
+    tokens: Mutex<f64>,
+    budget: RetryBudget,
+}
+
+impl BudgetState {
+    fn new(budget: RetryBudget) -> Self {
+        Self {
+            tokens: Mutex::new(budget.max_tokens),
+            budget,
+        }
+    }
+
+    fn on_success(&self) {
+        let mut tokens = self.tokens.lock().unwrap();
+        *tokens = (*tokens + self.budget.token_ratio).min(self.budget.max_tokens);
+    }
+
+    fn on_failure(&self) {
+        let mut tokens = self.tokens.lock().unwrap();
+        *tokens = (*tokens - 1.0).max(0.0);
+    }
+
+    fn can_retry(&self) -> bool {
+        *self.tokens.lock().unwrap() > self.budget.max_tokens / 2.0
+    }
+}
+
+/// A versioned [`VectorServiceClient`] that applies a [`CallPolicy`] to every call.
+#[derive(Debug, Clone)]
+pub struct PolicyClient<T> {
+    client: VectorServiceClient<T>,
+    policy: Arc<CallPolicy>,
+    budget: Arc<BudgetState>,
+}
+
+impl<T> PolicyClient<T>
+where
+    T: tonic::client::GrpcService<tonic::body::BoxBody> + Clone,
+    T::Error: Into<StdError>,
+    T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+    <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+{
+    pub fn new(client: VectorServiceClient<T>, policy: CallPolicy) -> Self {
+        let budget = policy.retry.clone().unwrap_or_default().budget;
+        Self {
+            client,
+            policy: Arc::new(policy),
+            budget: Arc::new(BudgetState::new(budget)),
+        }
+    }
+
+    pub fn client(&self) -> &VectorServiceClient<T> {
+        &self.client
+    }
+
+    pub async fn print(
+        &mut self,
+        request: impl tonic::IntoRequest<inner::PrintRequest>,
+    ) -> Result<Response<inner::PrintResponse>, Status> {
+        let client = self.client.clone();
+        self.call(Method::Print, request.into_request(), move |request| {
+            let mut client = client.clone();
+            async move { client.print(request).await }
+        })
+        .await
+    }
+
+    pub async fn sum(
+        &mut self,
+        request: impl tonic::IntoRequest<inner::SumRequest>,
+    ) -> Result<Response<inner::SumResponse>, Status> {
+        let client = self.client.clone();
+        self.call(Method::Sum, request.into_request(), move |request| {
+            let mut client = client.clone();
+            async move { client.sum(request).await }
+        })
+        .await
+    }
+
+    async fn call<Req, Resp, F, Fut>(
+        &self,
+        method: Method,
+        request: Request<Req>,
+        attempt: F,
+    ) -> Result<Response<Resp>, Status>
+    where
+        Req: Clone,
+        F: Fn(Request<Req>) -> Fut,
+        Fut: Future<Output = Result<Response<Resp>, Status>>,
+    {
+        let deadline = self
+            .policy
+            .timeouts
+            .get(&method)
+            .map(|timeout| Instant::now() + *timeout);
+        // every attempt carries the time left until the deadline as its `grpc-timeout`
+        let attempt = |request: &Request<Req>| {
+            let mut request = copy_request(request);
+            if let Some(deadline) = deadline {
+                request.set_timeout(deadline.saturating_duration_since(Instant::now()));
+            }
+            attempt(request)
+        };
+
+        let call = async {
+            match &self.policy.hedging {
+                Some(hedging) if hedging.methods.contains(&method) => {
+                    self.hedged(hedging, &request, attempt).await
+                }
+                _ => self.retried(&request, attempt).await,
+            }
+        };
+        let Some(deadline) = deadline else {
+            return call.await;
+        };
+        match tokio::time::timeout_at(deadline, call).await {
+            // the server enforces `grpc-timeout` too and reports it as CANCELLED, which may arrive first
+            Ok(Err(_)) if Instant::now() >= deadline => Err(deadline_exceeded()),
+            Ok(result) => result,
+            Err(_) => Err(deadline_exceeded()),
+        }
+    }
+
+    async fn retried<Req, Resp, F, Fut>(
+        &self,
+        request: &Request<Req>,
+        attempt: F,
+    ) -> Result<Response<Resp>, Status>
+    where
+        F: Fn(&Request<Req>) -> Fut,
+        Fut: Future<Output = Result<Response<Resp>, Status>>,
+    {
+        let Some(retry) = &self.policy.retry else {
+            return attempt(request).await;
+        };
+        let mut retries = 0;
+        loop {
+            match attempt(request).await {
+                Ok(response) => {
+                    self.budget.on_success();
+                    return Ok(response);
+                }
+                Err(status) => {
+                    // only failures that would be retried spend the budget
+                    if !retry.retryable_codes.contains(&status.code()) {
+                        return Err(status);
+                    }
+                    self.budget.on_failure();
+                    if retries + 1 >= retry.max_attempts || !self.budget.can_retry() {
+                        return Err(status);
+                    }
+                    sleep(retry.backoff(retries)).await;
+                    retries += 1;
+                }
+            }
+        }
+    }
+
+    // sends the request again every `delay` until an attempt succeeds, fails with a code that isn't
+    // retryable, or all of them failed
+    async fn hedged<Req, Resp, F, Fut>(
+        &self,
+        hedging: &HedgingPolicy,
+        request: &Request<Req>,
+        attempt: F,
+    ) -> Result<Response<Resp>, Status>
+    where
+        F: Fn(&Request<Req>) -> Fut,
+        Fut: Future<Output = Result<Response<Resp>, Status>>,
+    {
+        let mut in_flight = FuturesUnordered::new();
+        in_flight.push(attempt(request));
+        let mut launched = 1;
+        let hedge_timer = sleep(hedging.delay);
+        tokio::pin!(hedge_timer);
+
+        loop {
+            tokio::select! {
+                Some(result) = in_flight.next() => match result {
+                    Ok(response) => {
+                        self.budget.on_success();
+                        return Ok(response);
+                    }
+                    Err(status) => {
+                        if !hedging.retryable_codes.contains(&status.code()) {
+                            return Err(status);
+                        }
+                        self.budget.on_failure();
+                        if in_flight.is_empty() {
+                            if launched >= hedging.max_attempts || !self.budget.can_retry() {
+                                return Err(status);
+                            }
+                            in_flight.push(attempt(request));
+                            launched += 1;
+                            hedge_timer.as_mut().reset(Instant::now() + hedging.delay);
+                        }
+                    }
+                },
+                // every hedge after the first attempt is a retry the budget has to allow
+                _ = &mut hedge_timer, if launched < hedging.max_attempts && self.budget.can_retry() => {
+                    in_flight.push(attempt(request));
+                    launched += 1;
+                    hedge_timer.as_mut().reset(Instant::now() + hedging.delay);
+                }
+            }
+        }
+    }
+}
+
+fn deadline_exceeded() -> Status {
+    Status::deadline_exceeded("client deadline exceeded")
+}
+
+// tonic requests are not Clone, the extensions are dropped on every copy
+fn copy_request<R: Clone>(request: &Request<R>) -> Request<R> {
+    let mut copy = Request::new(request.get_ref().clone());
+    *copy.metadata_mut() = request.metadata().clone();
+    copy
+}
+
+// a uniformly distributed number in [0, 1), good enough for jitter
+pub(crate) fn random_fraction() -> f64 {
+    let random = RandomState::new().build_hasher().finish();
+    (random >> 11) as f64 / (1u64 << 53) as f64
+}
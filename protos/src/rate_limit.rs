@@ -0,0 +1,353 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Duration, Instant};
+
+use tonic::body::BoxBody;
+use tonic::codegen::http::{Request, Response};
+use tonic::codegen::{BoxFuture, Service};
+use tonic::metadata::MetadataValue;
+use tonic::transport::server::TcpConnectInfo;
+use tonic::Status;
+use tower_layer::Layer;
+
+use crate::auth::Principal;
+use crate::capabilities::{versioned_call, Method};
+use crate::errors::VectorError;
+use crate::wrappers::vector_service_client::SupportedVersion;
+
+// metadata key of the seconds a rejected caller should wait before its next call
+pub const RETRY_AFTER_HEADER: &str = "retry-after";
+
+// `ErrorInfo.reason` of the calls rejected by a rate limit
+pub const RATE_LIMITED_REASON: &str = "RATE_LIMITED";
+
+// how often buckets that filled up again are dropped, they'd start full anyway
+const SWEEP_INTERVAL: Duration = Duration::from_secs(10);
+
+// past this many buckets, the longest idle ones are evicted until a quarter of them is free again.
+// their clients start over with a full bucket
+const MAX_BUCKETS: usize = 10_000;
+
+/// A token bucket: up to `burst` calls at once, refilled at `per_second` calls per second.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RateLimit {
+    pub per_second: f64,
+    pub burst: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    // when the client last called
+    updated: Instant,
+}
+
+impl Bucket {
+    fn full(limit: RateLimit, now: Instant) -> Self {
+        Self {
+            tokens: limit.burst as f64,
+            updated: now,
+        }
+    }
+
+    fn tokens_at(&self, limit: RateLimit, now: Instant) -> f64 {
+        let elapsed = now.saturating_duration_since(self.updated).as_secs_f64();
+        (self.tokens + elapsed * limit.per_second).min(limit.burst as f64)
+    }
+
+    fn refill(&mut self, limit: RateLimit, now: Instant) {
+        self.tokens = self.tokens_at(limit, now);
+        self.updated = now;
+    }
+
+    // how long until the next token when the bucket is empty
+    fn take(&mut self, limit: RateLimit, now: Instant) -> Result<(), Duration> {
+        self.refill(limit, now);
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            return Ok(());
+        }
+        Err(
+            Duration::try_from_secs_f64((1.0 - self.tokens) / limit.per_second)
+                .unwrap_or(Duration::MAX),
+        )
+    }
+}
+
+// who a bucket belongs to: the authenticated principal, or else the peer's address
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Client {
+    Principal(String),
+    Peer(IpAddr),
+    Unknown,
+}
+
+impl Client {
+    fn of<B>(request: &Request<B>) -> Self {
+        if let Some(principal) = request.extensions().get::<Principal>() {
+            return Client::Principal(principal.name.clone());
+        }
+        request
+            .extensions()
+            .get::<TcpConnectInfo>()
+            .and_then(TcpConnectInfo::remote_addr)
+            .map_or(Client::Unknown, |address| Client::Peer(address.ip()))
+    }
+}
+
+impl fmt::Display for Client {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Client::Principal(name) => f.write_str(name),
+            Client::Peer(address) => write!(f, "{address}"),
+            Client::Unknown => f.write_str("an unknown client"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default)]
+struct Counts {
+    allowed: u64,
+    rejected: u64,
+}
+
+/// Calls let through and rejected by a [`RateLimiter`], per version and method.
+#[derive(Debug, Default)]
+pub struct RateLimitMetrics {
+    counts: Mutex<HashMap<(SupportedVersion, Method), Counts>>,
+}
+
+impl RateLimitMetrics {
+    pub fn allowed(&self, version: SupportedVersion, method: Method) -> u64 {
+        self.counts(version, method).allowed
+    }
+
+    pub fn rejected(&self, version: SupportedVersion, method: Method) -> u64 {
+        self.counts(version, method).rejected
+    }
+
+    fn counts(&self, version: SupportedVersion, method: Method) -> Counts {
+        let counts = self.counts.lock().unwrap();
+        counts.get(&(version, method)).copied().unwrap_or_default()
+    }
+
+    fn count(&self, version: SupportedVersion, method: Method, allowed: bool) {
+        let mut counts = self.counts.lock().unwrap();
+        let counts = counts.entry((version, method)).or_default();
+        match allowed {
+            true => counts.allowed += 1,
+            false => counts.rejected += 1,
+        }
+    }
+}
+
+type Scope = (Option<SupportedVersion>, Option<Method>);
+
+/// Token-bucket limits on the calls each client makes to the versioned vector services.
+///
+/// Limits are set per version and method, `None` standing for any. A call is limited by the most
+/// specific limit matching it, and every client has a bucket of its own for each limit.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimiter {
+    limits: Vec<(Scope, RateLimit)>,
+    metrics: Arc<RateLimitMetrics>,
+}
+
+impl RateLimiter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the limit of a version and method, replacing any limit set for the same ones.
+    pub fn limit(
+        mut self,
+        version: impl Into<Option<SupportedVersion>>,
+        method: impl Into<Option<Method>>,
+        limit: RateLimit,
+    ) -> Self {
+        let scope = (version.into(), method.into());
+        self.limits.retain(|(existing, _)| *existing != scope);
+        self.limits.push((scope, limit));
+        self
+    }
+
+    pub fn metrics(&self) -> Arc<RateLimitMetrics> {
+        self.metrics.clone()
+    }
+
+    /// A layer with buckets of its own, shared by every connection it serves.
+    pub fn layer(&self) -> RateLimitLayer {
+        RateLimitLayer {
+            limiter: Some(Arc::new(Limiter {
+                limits: self.limits.clone(),
+                buckets: Mutex::new(Buckets::new(Instant::now())),
+                metrics: self.metrics.clone(),
+            })),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct Buckets {
+    // by client and index of the limit
+    buckets: HashMap<(Client, usize), Bucket>,
+    swept: Instant,
+}
+
+impl Buckets {
+    fn new(now: Instant) -> Self {
+        Self {
+            buckets: HashMap::new(),
+            swept: now,
+        }
+    }
+
+    fn take(
+        &mut self,
+        client: Client,
+        index: usize,
+        limits: &[(Scope, RateLimit)],
+        now: Instant,
+    ) -> Result<(), Duration> {
+        if now.saturating_duration_since(self.swept) >= SWEEP_INTERVAL {
+            self.swept = now;
+            self.buckets.retain(|(_, index), bucket| {
+                let limit = limits[*index].1;
+                bucket.tokens_at(limit, now) < limit.burst as f64
+            });
+        }
+        if self.buckets.len() >= MAX_BUCKETS {
+            self.evict_idle();
+        }
+        let limit = limits[index].1;
+        self.buckets
+            .entry((client, index))
+            .or_insert_with(|| Bucket::full(limit, now))
+            .take(limit, now)
+    }
+
+    // keeps the buckets of the clients seen last, ties with the oldest kept one are evicted too
+    fn evict_idle(&mut self) {
+        let mut updated: Vec<_> = self.buckets.values().map(|bucket| bucket.updated).collect();
+        let evicted = updated.len() - MAX_BUCKETS * 3 / 4;
+        let (_, &mut cutoff, _) = updated.select_nth_unstable(evicted);
+        self.buckets.retain(|_, bucket| bucket.updated > cutoff);
+    }
+}
+
+#[derive(Debug)]
+struct Limiter {
+    limits: Vec<(Scope, RateLimit)>,
+    buckets: Mutex<Buckets>,
+    metrics: Arc<RateLimitMetrics>,
+}
+
+impl Limiter {
+    // the index of the limit of the call
+    fn find(&self, version: SupportedVersion, method: Method) -> Option<usize> {
+        let scopes = [
+            (Some(version), Some(method)),
+            (Some(version), None),
+            (None, Some(method)),
+            (None, None),
+        ];
+        scopes.into_iter().find_map(|scope| {
+            self.limits
+                .iter()
+                .position(|(existing, _)| *existing == scope)
+        })
+    }
+
+    fn check(
+        &self,
+        client: Client,
+        version: SupportedVersion,
+        method: Method,
+    ) -> Result<(), Status> {
+        let Some(index) = self.find(version, method) else {
+            self.metrics.count(version, method, true);
+            return Ok(());
+        };
+        let taken =
+            self.buckets
+                .lock()
+                .unwrap()
+                .take(client.clone(), index, &self.limits, Instant::now());
+        self.metrics.count(version, method, taken.is_ok());
+        taken.map_err(|wait| {
+            let mut status: Status = VectorError::ResourceExhausted {
+                reason: RATE_LIMITED_REASON.to_string(),
+                message: format!(
+                    "{client} is over its rate limit for {} {}",
+                    version.api_version().name,
+                    method.name()
+                ),
+                retry_after: Some(wait),
+            }
+            .into();
+            // also as plain metadata, for callers that don't decode details
+            let seconds = wait.as_secs_f64().ceil() as u64;
+            status
+                .metadata_mut()
+                .insert(RETRY_AFTER_HEADER, MetadataValue::from(seconds));
+            status
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RateLimitLayer {
+    // every call goes through when `None`
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl RateLimitLayer {
+    pub fn disabled() -> Self {
+        Self { limiter: None }
+    }
+}
+
+impl<S> Layer<S> for RateLimitLayer {
+    type Service = RateLimitService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+        }
+    }
+}
+
+/// Rate limits the calls to the versioned vector services, after authentication when there is any.
+#[derive(Debug, Clone)]
+pub struct RateLimitService<S> {
+    inner: S,
+    limiter: Option<Arc<Limiter>>,
+}
+
+impl<S, B> Service<Request<B>> for RateLimitService<S>
+where
+    S: Service<Request<B>, Response = Response<BoxBody>>,
+    S::Future: Send + 'static,
+{
+    type Response = Response<BoxBody>;
+    type Error = S::Error;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, request: Request<B>) -> Self::Future {
+        let called = versioned_call(request.uri().path());
+        if let (Some(limiter), Some((version, method))) = (&self.limiter, called) {
+            if let Err(status) = limiter.check(Client::of(&request), version, method) {
+                return Box::pin(async move { Ok(status.to_http()) });
+            }
+        }
+        Box::pin(self.inner.call(request))
+    }
+}
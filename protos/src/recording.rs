@@ -0,0 +1,435 @@
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex};
+use std::task::{Context, Poll};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+
+use futures_util::future::poll_fn;
+use prost::Message;
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, HeaderName, HeaderValue, Request, Response};
+use tonic::codegen::{Body, BoxFuture, Bytes, Service, StdError};
+use tonic::transport::{Body as TransportBody, Channel};
+use tonic::{Code, Status};
+use tower_layer::Layer;
+
+pub use crate::api::recording::{MetadataEntry, RecordedCall};
+use crate::auth::API_KEY_HEADER;
+use crate::capabilities::versioned_call;
+use crate::serving::take_ready;
+use crate::simulator::boxed;
+use crate::wrappers::vector_service_client::SupportedVersion;
+
+/// Appends every call to a versioned vector service to a recording, see [`read_recording`] and [`replay`].
+///
+/// Calls are written as they complete, so the file is always a valid recording.
+/// The credentials callers send are left out unless kept with [`Recorder::keep_credentials`].
+#[derive(Debug, Clone)]
+pub struct Recorder {
+    file: Arc<Mutex<BufWriter<File>>>,
+    keep_credentials: bool,
+}
+
+// headers that carry the caller's credentials
+const CREDENTIAL_HEADERS: [&str; 2] = ["authorization", API_KEY_HEADER];
+
+impl Recorder {
+    /// Starts a new recording at `path`, replacing any file already there.
+    pub fn create(path: impl AsRef<Path>) -> io::Result<Self> {
+        Ok(Self {
+            file: Arc::new(Mutex::new(BufWriter::new(File::create(path)?))),
+            keep_credentials: false,
+        })
+    }
+
+    /// Records the `authorization` and `x-api-key` headers too, so a replay authenticates as the
+    /// original callers. The recording is then as secret as their credentials.
+    #[must_use]
+    pub fn keep_credentials(mut self) -> Self {
+        self.keep_credentials = true;
+        self
+    }
+
+    pub fn layer(&self) -> RecorderLayer {
+        RecorderLayer {
+            recorder: self.clone(),
+        }
+    }
+
+    // a failing recording must not fail the calls it records
+    fn write(&self, call: &RecordedCall) {
+        let mut file = self.file.lock().unwrap();
+        let _ = file
+            .write_all(&call.encode_length_delimited_to_vec())
+            .and_then(|_| file.flush());
+    }
+}
+
+/// Every call of a recording, in the order they completed.
+pub fn read_recording(path: impl AsRef<Path>) -> io::Result<Vec<RecordedCall>> {
+    let contents = std::fs::read(path)?;
+    let mut remaining = contents.as_slice();
+    let mut calls = Vec::new();
+    while !remaining.is_empty() {
+        let call = RecordedCall::decode_length_delimited(&mut remaining)
+            .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+        calls.push(call);
+    }
+    Ok(calls)
+}
+
+#[derive(Debug, Clone)]
+pub struct RecorderLayer {
+    recorder: Recorder,
+}
+
+impl<S> Layer<S> for RecorderLayer {
+    type Service = RecordingService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RecordingService {
+            inner,
+            recorder: self.recorder.clone(),
+        }
+    }
+}
+
+/// Records the calls of every versioned vector service, other services are passed through.
+#[derive(Debug, Clone)]
+pub struct RecordingService<S> {
+    inner: S,
+    recorder: Recorder,
+}
+
+impl<S, ResBody> Service<Request<TransportBody>> for RecordingService<S>
+where
+    S: Service<Request<TransportBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<StdError>,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<StdError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = StdError;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<TransportBody>) -> Self::Future {
+        let called = versioned_call(request.uri().path());
+        let mut inner = take_ready(&mut self.inner);
+        let recorder = self.recorder.clone();
+        Box::pin(async move {
+            let Some((version, method)) = called else {
+                let response = inner.call(request).await.map_err(Into::into)?;
+                return Ok(response.map(boxed));
+            };
+            let started = Instant::now();
+            let (parts, body) = request.into_parts();
+            // every method is unary, buffering the request delays nothing
+            let body = collect(body).await?;
+            let call = RecordedCall {
+                version: version.api_version().name.to_string(),
+                method: method.name().to_string(),
+                metadata: parts
+                    .headers
+                    .iter()
+                    .filter(|(key, _)| {
+                        recorder.keep_credentials || !CREDENTIAL_HEADERS.contains(&key.as_str())
+                    })
+                    .filter_map(|(key, value)| {
+                        Some(MetadataEntry {
+                            key: key.to_string(),
+                            value: value.to_str().ok()?.to_string(),
+                        })
+                    })
+                    .collect(),
+                request: body.to_vec(),
+                started_at_us: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .unwrap_or_default()
+                    .as_micros() as u64,
+                ..Default::default()
+            };
+
+            let response = match inner
+                .call(Request::from_parts(parts, TransportBody::from(body)))
+                .await
+            {
+                Ok(response) => response,
+                Err(error) => {
+                    let error = error.into();
+                    finish(&recorder, call, started, Status::unknown(error.to_string()));
+                    return Err(error);
+                }
+            };
+            // errors come back without a body, their status in the headers
+            if let Some(status) = Status::from_header_map(response.headers()) {
+                finish(&recorder, call, started, status);
+                return Ok(response.map(boxed));
+            }
+            Ok(response.map(|body| {
+                BoxBody::new(RecordingBody {
+                    inner: boxed(body),
+                    pending: Some(PendingCall {
+                        recorder,
+                        call,
+                        started,
+                    }),
+                })
+            }))
+        })
+    }
+}
+
+async fn collect<B>(mut body: B) -> Result<Bytes, B::Error>
+where
+    B: Body<Data = Bytes> + Unpin,
+{
+    let mut collected = Vec::new();
+    while let Some(data) = body.data().await {
+        collected.extend_from_slice(&data?);
+    }
+    Ok(collected.into())
+}
+
+fn finish(recorder: &Recorder, mut call: RecordedCall, started: Instant, status: Status) {
+    call.code = status.code() as i32;
+    call.message = status.message().to_string();
+    call.duration_us = started.elapsed().as_micros() as u64;
+    recorder.write(&call);
+}
+
+struct PendingCall {
+    recorder: Recorder,
+    call: RecordedCall,
+    started: Instant,
+}
+
+// copies the response as it is sent, and records the call once its trailers are ready
+struct RecordingBody {
+    inner: BoxBody,
+    // `None` once recorded
+    pending: Option<PendingCall>,
+}
+
+impl RecordingBody {
+    fn finish(&mut self, status: Status) {
+        if let Some(PendingCall {
+            recorder,
+            call,
+            started,
+        }) = self.pending.take()
+        {
+            finish(&recorder, call, started, status);
+        }
+    }
+}
+
+impl Body for RecordingBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        let polled = Pin::new(&mut self.inner).poll_data(cx);
+        if let Poll::Ready(Some(Ok(data))) = &polled {
+            if let Some(pending) = &mut self.pending {
+                pending.call.response.extend_from_slice(data);
+            }
+        }
+        polled
+    }
+
+    fn poll_trailers(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        let polled = Pin::new(&mut self.inner).poll_trailers(cx);
+        match &polled {
+            Poll::Ready(Ok(trailers)) => {
+                let status = trailers
+                    .as_ref()
+                    .and_then(Status::from_header_map)
+                    .unwrap_or_else(|| Status::unknown("the response ended without a status"));
+                self.finish(status);
+            }
+            Poll::Ready(Err(status)) => self.finish(status.clone()),
+            Poll::Pending => {}
+        }
+        polled
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+}
+
+impl Drop for RecordingBody {
+    fn drop(&mut self) {
+        self.finish(Status::cancelled(
+            "the response was dropped before it was sent",
+        ));
+    }
+}
+
+/// What a call was answered with, as recorded or replayed.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Outcome {
+    pub code: Code,
+    pub message: String,
+    // the gRPC-framed response body
+    pub response: Bytes,
+}
+
+impl fmt::Display for Outcome {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.code {
+            Code::Ok => write!(f, "Ok ({} bytes)", self.response.len()),
+            code => write!(f, "{code:?} {:?}", self.message),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Difference {
+    // the call's position in the recording
+    pub index: usize,
+    pub method: String,
+    pub recorded: Outcome,
+    pub replayed: Outcome,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct VersionReport {
+    pub calls: usize,
+    pub differences: Vec<Difference>,
+}
+
+/// The calls of a replay whose responses differ from the recording, by version name.
+#[derive(Debug, Clone, Default)]
+pub struct ReplayReport {
+    pub versions: BTreeMap<String, VersionReport>,
+}
+
+impl ReplayReport {
+    pub fn is_clean(&self) -> bool {
+        self.versions
+            .values()
+            .all(|version| version.differences.is_empty())
+    }
+}
+
+impl fmt::Display for ReplayReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (version, report) in &self.versions {
+            writeln!(
+                f,
+                "{version}: {} calls, {} different",
+                report.calls,
+                report.differences.len()
+            )?;
+            for difference in &report.differences {
+                writeln!(
+                    f,
+                    "  #{} {}: recorded {}, replayed {}",
+                    difference.index, difference.method, difference.recorded, difference.replayed
+                )?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Sends every recorded call to `channel` with its recorded metadata, one after the other,
+/// and compares the responses with the recorded ones. Credentials are only sent when the
+/// recorder kept them.
+pub async fn replay(calls: &[RecordedCall], channel: Channel) -> ReplayReport {
+    let mut report = ReplayReport::default();
+    for (index, call) in calls.iter().enumerate() {
+        let recorded = Outcome {
+            code: Code::from_i32(call.code),
+            message: call.message.clone(),
+            response: call.response.clone().into(),
+        };
+        let replayed = replay_call(call, channel.clone()).await;
+        let version = report.versions.entry(call.version.clone()).or_default();
+        version.calls += 1;
+        if recorded != replayed {
+            version.differences.push(Difference {
+                index,
+                method: call.method.clone(),
+                recorded,
+                replayed,
+            });
+        }
+    }
+    report
+}
+
+async fn replay_call(call: &RecordedCall, mut channel: Channel) -> Outcome {
+    let failed = |status: Status| Outcome {
+        code: status.code(),
+        message: status.message().to_string(),
+        response: Bytes::new(),
+    };
+    let Some(version) = SupportedVersion::from_name(&call.version) else {
+        return failed(Status::unimplemented(format!(
+            "{} is not a version of this build",
+            call.version
+        )));
+    };
+
+    let mut request = Request::new(boxed(TransportBody::from(call.request.clone())));
+    *request.method_mut() = tonic::codegen::http::Method::POST;
+    *request.uri_mut() = match format!("/{}/{}", version.service_name(), call.method).parse() {
+        Ok(uri) => uri,
+        Err(error) => return failed(Status::invalid_argument(format!("{error}"))),
+    };
+    for entry in &call.metadata {
+        // entries that aren't valid headers can't have been received as such
+        if let (Ok(key), Ok(value)) = (
+            HeaderName::try_from(entry.key.as_str()),
+            HeaderValue::try_from(entry.value.as_str()),
+        ) {
+            request.headers_mut().append(key, value);
+        }
+    }
+
+    let response = async {
+        poll_fn(|cx| channel.poll_ready(cx)).await?;
+        channel.call(request).await
+    };
+    let response = match response.await {
+        Ok(response) => response,
+        Err(error) => return failed(Status::from_error(error.into())),
+    };
+    if let Some(status) = Status::from_header_map(response.headers()) {
+        return failed(status);
+    }
+    let mut body = response.into_body();
+    let collected = match collect(&mut body).await {
+        Ok(collected) => collected,
+        Err(error) => return failed(Status::from_error(error.into())),
+    };
+    let status = match body.trailers().await {
+        Ok(trailers) => trailers
+            .as_ref()
+            .and_then(Status::from_header_map)
+            .unwrap_or_else(|| Status::unknown("the response ended without a status")),
+        Err(error) => Status::from_error(error.into()),
+    };
+    Outcome {
+        code: status.code(),
+        message: status.message().to_string(),
+        response: collected,
+    }
+}
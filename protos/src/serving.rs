@@ -0,0 +1,87 @@
+// the server every deployment runs, shared by the binaries and `testing::TestServer` so tests go through
+// the same middleware as production
+
+use tonic::transport::server::Router;
+use tonic::transport::Server;
+use tower_layer::{Identity, Stack};
+
+use crate::auth::{AuthLayer, Authenticator};
+use crate::concurrency::{ConcurrencyLimitLayer, ConcurrencyLimits};
+use crate::deadline::DeadlineLayer;
+use crate::rate_limit::{RateLimitLayer, RateLimiter};
+use crate::validation::{Validated, ValidationConfig};
+use crate::wrappers::vector_service_server::{self, VectorService, VersionOverrides};
+
+/// Optional middleware of the server, all off by default.
+#[derive(Debug, Default)]
+pub struct ServeOptions {
+    // only callers it authorizes reach the versioned services
+    pub authenticator: Option<Authenticator>,
+    // limits callers by principal when authenticated, by address otherwise
+    pub rate_limiter: Option<RateLimiter>,
+    // calls past a limit wait in its queue, and are shed once the queue is full
+    pub concurrency: ConcurrencyLimits,
+    // checked before the inner service sees a request, violations come back as `BadRequest` details
+    pub validation: ValidationConfig,
+}
+
+/// The layers of [`router_with_layer`], `L` being the caller's own.
+pub type ServeLayers<L = Identity> = Stack<
+    ConcurrencyLimitLayer,
+    Stack<RateLimitLayer, Stack<AuthLayer, Stack<L, Stack<DeadlineLayer, Identity>>>>,
+>;
+
+/// Every version of `service` and the health service, behind the middleware `options` turns on.
+pub async fn router<T: VectorService>(
+    service: T,
+    overrides: VersionOverrides,
+    options: ServeOptions,
+) -> Router<ServeLayers> {
+    router_with_layer(service, overrides, options, Identity::new()).await
+}
+
+/// Like [`router`], with `layer` seeing every call right after its deadline is stamped, before
+/// any of the other middleware can reject it.
+pub async fn router_with_layer<T, L>(
+    service: T,
+    overrides: VersionOverrides,
+    options: ServeOptions,
+    layer: L,
+) -> Router<ServeLayers<L>>
+where
+    T: VectorService,
+    L: Clone,
+{
+    let auth = options
+        .authenticator
+        .map_or_else(AuthLayer::disabled, |authenticator| authenticator.layer());
+    let rate_limit = options
+        .rate_limiter
+        .map_or_else(RateLimitLayer::disabled, |rate_limiter| {
+            rate_limiter.layer()
+        });
+    let concurrency = options.concurrency.global_layer();
+    let add_services = vector_service_server::add_limited_services_to_server(
+        Validated::new(service, options.validation),
+        overrides,
+        options.concurrency,
+    );
+    // outermost first, deadlines are counted from arrival, rate limits need the authenticated
+    // principal, and rejected calls shouldn't take a slot of the concurrency limit
+    add_services(
+        Server::builder()
+            .layer(DeadlineLayer)
+            .layer(layer)
+            .layer(auth)
+            .layer(rate_limit)
+            .layer(concurrency),
+    )
+    .add_service(vector_service_server::health_service().await)
+}
+
+// middleware answering in a future of its own takes the service it polled ready into that future, and
+// leaves a clone for the next call. the clone may not be ready yet, tower polls it again before using it
+pub(crate) fn take_ready<S: Clone>(service: &mut S) -> S {
+    let clone = service.clone();
+    std::mem::replace(service, clone)
+}
@@ -0,0 +1,225 @@
+use std::collections::VecDeque;
+use std::fmt::Debug;
+use std::future::Future;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+
+use tokio::sync::oneshot;
+use tonic::{Request, Response, Status};
+
+use crate::api::inner::{PrintRequest, PrintResponse, SumRequest, SumResponse, VectorService};
+use crate::api_version::ApiVersion;
+use crate::auth::principal;
+use crate::deadline::{deadline, CallScope};
+
+#[derive(Debug, Clone)]
+pub struct ShadowConfig {
+    // how far two sums may be apart and still match, relative to the larger one once it is above 1
+    pub sum_tolerance: f32,
+}
+
+impl Default for ShadowConfig {
+    fn default() -> Self {
+        Self {
+            sum_tolerance: 1e-4,
+        }
+    }
+}
+
+// how many of the latest divergences the metrics keep
+const KEPT_DIVERGENCES: usize = 16;
+
+/// A call the shadow answered differently, with both answers described.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    pub method: &'static str,
+    pub primary: String,
+    pub shadow: String,
+}
+
+/// Counts the calls a [`Shadowed`] service compared, shared with whoever reports them.
+#[derive(Debug, Default)]
+pub struct ShadowMetrics {
+    compared: AtomicU64,
+    diverged: AtomicU64,
+    latest: Mutex<VecDeque<Divergence>>,
+}
+
+impl ShadowMetrics {
+    pub fn compared(&self) -> u64 {
+        self.compared.load(Ordering::Relaxed)
+    }
+
+    pub fn diverged(&self) -> u64 {
+        self.diverged.load(Ordering::Relaxed)
+    }
+
+    /// The latest divergences, oldest first.
+    pub fn latest_divergences(&self) -> Vec<Divergence> {
+        self.latest.lock().unwrap().iter().cloned().collect()
+    }
+
+    fn record(&self, divergence: Divergence) {
+        self.diverged.fetch_add(1, Ordering::Relaxed);
+        let mut latest = self.latest.lock().unwrap();
+        if latest.len() == KEPT_DIVERGENCES {
+            latest.pop_front();
+        }
+        latest.push_back(divergence);
+    }
+}
+
+/// An inner `VectorService` answering with `primary`, while every request also goes to `shadow`.
+///
+/// The shadow runs alongside the primary and never delays or changes its answer, divergences are
+/// only counted, and the latest kept in its [`ShadowMetrics`]. Serving it through the version
+/// adapters shadows every version at once.
+pub struct Shadowed<P, S> {
+    primary: P,
+    shadow: Arc<S>,
+    config: ShadowConfig,
+    metrics: Arc<ShadowMetrics>,
+}
+
+impl<P: VectorService, S: VectorService> Shadowed<P, S> {
+    pub fn new(primary: P, shadow: S, config: ShadowConfig) -> Self {
+        Self {
+            primary,
+            shadow: Arc::new(shadow),
+            config,
+            metrics: Arc::default(),
+        }
+    }
+
+    pub fn metrics(&self) -> Arc<ShadowMetrics> {
+        self.metrics.clone()
+    }
+
+    // starts the shadow call, the returned sender takes the primary's answer to compare with
+    fn start_shadow<Req, Res, F, Fut>(
+        &self,
+        method: &'static str,
+        request: &Request<Req>,
+        call: F,
+    ) -> oneshot::Sender<Result<Res, Status>>
+    where
+        Req: Clone,
+        Res: Matches + Debug + Send + 'static,
+        F: FnOnce(Arc<S>, Request<Req>) -> Fut,
+        Fut: Future<Output = Result<Response<Res>, Status>> + Send + 'static,
+    {
+        let mut copy = copy_request(request);
+        // a scope of its own, the primary's token is cancelled as soon as the primary answers
+        let scope = CallScope::enter(&mut copy);
+        let shadowed = call(self.shadow.clone(), copy);
+        let tolerance = self.config.sum_tolerance;
+        let metrics = self.metrics.clone();
+        let (primary, primary_result) = oneshot::channel();
+        tokio::spawn(async move {
+            // held to the primary's deadline, and cancelled once it is over
+            let shadowed = scope.run(shadowed).await.map(Response::into_inner);
+            // the primary call was dropped before it answered
+            let Ok(primary) = primary_result.await else {
+                return;
+            };
+            metrics.compared.fetch_add(1, Ordering::Relaxed);
+            if !matches(&primary, &shadowed, tolerance) {
+                metrics.record(Divergence {
+                    method,
+                    primary: describe(&primary),
+                    shadow: describe(&shadowed),
+                });
+            }
+        });
+        primary
+    }
+}
+
+#[tonic::async_trait]
+impl<P: VectorService, S: VectorService> VectorService for Shadowed<P, S> {
+    async fn print(
+        &self,
+        request: Request<PrintRequest>,
+    ) -> Result<Response<PrintResponse>, Status> {
+        let compare = self.start_shadow("print", &request, |shadow, request| async move {
+            shadow.print(request).await
+        });
+        let result = self.primary.print(request).await;
+        let _ = compare.send(answer(&result));
+        result
+    }
+
+    async fn sum(&self, request: Request<SumRequest>) -> Result<Response<SumResponse>, Status> {
+        let compare = self.start_shadow("sum", &request, |shadow, request| async move {
+            shadow.sum(request).await
+        });
+        let result = self.primary.sum(request).await;
+        let _ = compare.send(answer(&result));
+        result
+    }
+}
+
+// extensions can't be cloned, these are the ones the inner service reads besides the
+// cancellation token, which the shadow gets one of its own of
+fn copy_request<T: Clone>(request: &Request<T>) -> Request<T> {
+    let mut copy = Request::new(request.get_ref().clone());
+    *copy.metadata_mut() = request.metadata().clone();
+    if let Some(version) = request.extensions().get::<ApiVersion>() {
+        copy.extensions_mut().insert(*version);
+    }
+    if let Some(principal) = principal(request) {
+        copy.extensions_mut().insert(principal.clone());
+    }
+    if let Some(deadline) = deadline(request) {
+        copy.extensions_mut().insert(deadline);
+    }
+    copy
+}
+
+fn answer<T: Clone>(result: &Result<Response<T>, Status>) -> Result<T, Status> {
+    result
+        .as_ref()
+        .map(|response| response.get_ref().clone())
+        .map_err(Status::clone)
+}
+
+trait Matches {
+    fn matches(&self, other: &Self, sum_tolerance: f32) -> bool;
+}
+
+impl Matches for PrintResponse {
+    fn matches(&self, other: &Self, _sum_tolerance: f32) -> bool {
+        self == other
+    }
+}
+
+impl Matches for SumResponse {
+    fn matches(&self, other: &Self, sum_tolerance: f32) -> bool {
+        self.sum.len() == other.sum.len()
+            && self.sum.iter().zip(&other.sum).all(|(&a, &b)| {
+                a == b
+                    || (a.is_nan() && b.is_nan())
+                    || (a - b).abs() <= sum_tolerance * a.abs().max(b.abs()).max(1.0)
+            })
+    }
+}
+
+// errors match on their code alone, messages are free to change between implementations
+fn matches<T: Matches>(
+    primary: &Result<T, Status>,
+    shadow: &Result<T, Status>,
+    sum_tolerance: f32,
+) -> bool {
+    match (primary, shadow) {
+        (Ok(primary), Ok(shadow)) => primary.matches(shadow, sum_tolerance),
+        (Err(primary), Err(shadow)) => primary.code() == shadow.code(),
+        _ => false,
+    }
+}
+
+fn describe<T: Debug>(result: &Result<T, Status>) -> String {
+    match result {
+        Ok(response) => format!("{response:?}"),
+        Err(status) => format!("{:?} {:?}", status.code(), status.message()),
+    }
+}
@@ -0,0 +1,421 @@
+use std::fmt;
+use std::pin::Pin;
+use std::sync::{Arc, RwLock};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use tonic::body::BoxBody;
+use tonic::codegen::http::{HeaderMap, Request, Response};
+use tonic::codegen::{Body, BoxFuture, Bytes, Service, StdError};
+use tonic::{Code, Status};
+use tower_layer::Layer;
+
+use crate::api::admin;
+use crate::api::admin::fault_admin_server::{FaultAdmin, FaultAdminServer};
+use crate::capabilities::{versioned_call, Method};
+use crate::policy::random_fraction;
+use crate::serving::take_ready;
+use crate::wrappers::vector_service_client::SupportedVersion;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Latency {
+    Fixed(Duration),
+    Uniform { min: Duration, max: Duration },
+    // most samples are short with a long tail, like queueing delays
+    Exponential { mean: Duration },
+}
+
+impl Latency {
+    pub fn sample(&self) -> Duration {
+        match *self {
+            Latency::Fixed(latency) => latency,
+            Latency::Uniform { min, max } => {
+                min.saturating_add(scale(max.saturating_sub(min), random_fraction()))
+            }
+            Latency::Exponential { mean } => scale(mean, -(1.0 - random_fraction()).ln()),
+        }
+    }
+}
+
+// saturates rather than panicking on a product too long for a `Duration`
+fn scale(duration: Duration, factor: f64) -> Duration {
+    Duration::try_from_secs_f64(duration.as_secs_f64() * factor).unwrap_or(Duration::MAX)
+}
+
+// the longest latency the admin service accepts
+const MAX_LATENCY_MS: u64 = 60 * 60 * 1000;
+
+/// What happens to the calls a fault applies to, each effect drawn independently per call.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Fault {
+    pub latency: Option<Latency>,
+    // tried in order, the first one drawn answers the call instead of the service
+    pub errors: Vec<(Code, f64)>,
+    // the stream is reset before the service is called
+    pub reset_probability: f64,
+    // the response ends in the middle of its message, without a status
+    pub truncate_probability: f64,
+}
+
+/// The calls a [`Fault`] applies to, every version or method when `None`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FaultTarget {
+    pub version: Option<SupportedVersion>,
+    pub method: Option<Method>,
+}
+
+impl FaultTarget {
+    // calls to other services are never faulted
+    fn from_path(path: &str) -> Option<Self> {
+        let (version, method) = versioned_call(path)?;
+        Some(Self {
+            version: Some(version),
+            method: Some(method),
+        })
+    }
+
+    // the most specific target first
+    fn candidates(self) -> [Self; 4] {
+        [
+            self,
+            Self {
+                method: None,
+                ..self
+            },
+            Self {
+                version: None,
+                ..self
+            },
+            Self::default(),
+        ]
+    }
+}
+
+/// The faults a simulator injects, shared between its [`FaultLayer`] and its admin service.
+///
+/// One fault is kept per target; a call gets the fault of the most specific target matching it.
+#[derive(Debug, Clone, Default)]
+pub struct Faults {
+    rules: Arc<RwLock<Vec<(FaultTarget, Fault)>>>,
+}
+
+impl Faults {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn set(&self, target: FaultTarget, fault: Fault) {
+        let mut rules = self.rules.write().unwrap();
+        match rules.iter_mut().find(|(existing, _)| *existing == target) {
+            Some((_, existing)) => *existing = fault,
+            None => rules.push((target, fault)),
+        }
+    }
+
+    pub fn clear(&self) {
+        self.rules.write().unwrap().clear();
+    }
+
+    /// Every fault, in the order their targets were first set.
+    pub fn list(&self) -> Vec<(FaultTarget, Fault)> {
+        self.rules.read().unwrap().clone()
+    }
+
+    pub fn layer(&self) -> FaultLayer {
+        FaultLayer {
+            faults: self.clone(),
+        }
+    }
+
+    /// The `API.admin.FaultAdmin` service, to change the faults of a running simulator.
+    pub fn admin_service(&self) -> FaultAdminServer<Faults> {
+        FaultAdminServer::new(self.clone())
+    }
+
+    fn find(&self, target: FaultTarget) -> Option<Fault> {
+        let rules = self.rules.read().unwrap();
+        target.candidates().into_iter().find_map(|candidate| {
+            rules
+                .iter()
+                .find(|(target, _)| *target == candidate)
+                .map(|(_, fault)| fault.clone())
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct FaultLayer {
+    faults: Faults,
+}
+
+impl<S> Layer<S> for FaultLayer {
+    type Service = FaultService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        FaultService {
+            inner,
+            faults: self.faults.clone(),
+        }
+    }
+}
+
+/// Injects the current [`Faults`] into the calls of every versioned vector service.
+#[derive(Debug, Clone)]
+pub struct FaultService<S> {
+    inner: S,
+    faults: Faults,
+}
+
+impl<S, ReqBody, ResBody> Service<Request<ReqBody>> for FaultService<S>
+where
+    S: Service<Request<ReqBody>, Response = Response<ResBody>> + Clone + Send + 'static,
+    S::Future: Send,
+    S::Error: Into<StdError>,
+    ReqBody: Send + 'static,
+    ResBody: Body<Data = Bytes> + Send + 'static,
+    ResBody::Error: Into<StdError>,
+{
+    type Response = Response<BoxBody>;
+    type Error = StdError;
+    type Future = BoxFuture<Self::Response, Self::Error>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request<ReqBody>) -> Self::Future {
+        let fault = FaultTarget::from_path(request.uri().path())
+            .and_then(|target| self.faults.find(target))
+            .unwrap_or_default();
+        let mut inner = take_ready(&mut self.inner);
+        Box::pin(async move {
+            if let Some(latency) = fault.latency {
+                tokio::time::sleep(latency.sample()).await;
+            }
+            if happens(fault.reset_probability) {
+                return Err(InjectedReset.into());
+            }
+            if let Some((code, _)) = fault.errors.iter().find(|(_, p)| happens(*p)) {
+                return Ok(Status::new(*code, "injected by the simulator").to_http());
+            }
+            let response = inner.call(request).await.map_err(Into::into)?;
+            let truncate = happens(fault.truncate_probability);
+            Ok(response.map(|body| match truncate {
+                true => BoxBody::new(TruncatedBody {
+                    inner: boxed(body),
+                    done: false,
+                }),
+                false => boxed(body),
+            }))
+        })
+    }
+}
+
+// tonic's own `boxed` is private
+pub(crate) fn boxed<B>(body: B) -> BoxBody
+where
+    B: Body<Data = Bytes> + Send + 'static,
+    B::Error: Into<StdError>,
+{
+    body.map_err(|error| Status::from_error(error.into()))
+        .boxed_unsync()
+}
+
+fn happens(probability: f64) -> bool {
+    random_fraction() < probability
+}
+
+// not a `Status`, so tonic can't answer it and the stream is reset instead
+#[derive(Debug)]
+struct InjectedReset;
+
+impl fmt::Display for InjectedReset {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "stream reset injected by the simulator")
+    }
+}
+
+impl std::error::Error for InjectedReset {}
+
+// passes on half of the first data frame and ends there, without the trailers holding the status
+struct TruncatedBody {
+    inner: BoxBody,
+    done: bool,
+}
+
+impl Body for TruncatedBody {
+    type Data = Bytes;
+    type Error = Status;
+
+    fn poll_data(
+        mut self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+    ) -> Poll<Option<Result<Self::Data, Self::Error>>> {
+        if self.done {
+            return Poll::Ready(None);
+        }
+        match Pin::new(&mut self.inner).poll_data(cx) {
+            Poll::Ready(Some(Ok(mut data))) => {
+                self.done = true;
+                data.truncate(data.len() / 2);
+                Poll::Ready(Some(Ok(data)))
+            }
+            other => other,
+        }
+    }
+
+    fn poll_trailers(
+        self: Pin<&mut Self>,
+        _cx: &mut Context<'_>,
+    ) -> Poll<Result<Option<HeaderMap>, Self::Error>> {
+        Poll::Ready(Ok(None))
+    }
+
+    fn is_end_stream(&self) -> bool {
+        self.done
+    }
+}
+
+#[tonic::async_trait]
+impl FaultAdmin for Faults {
+    async fn set_fault(
+        &self,
+        request: tonic::Request<admin::SetFaultRequest>,
+    ) -> Result<tonic::Response<admin::SetFaultResponse>, Status> {
+        let fault = request
+            .into_inner()
+            .fault
+            .ok_or_else(|| Status::invalid_argument("a fault is required"))?;
+        let (target, fault) = from_proto(fault)?;
+        self.set(target, fault);
+        Ok(tonic::Response::new(admin::SetFaultResponse {}))
+    }
+
+    async fn clear_faults(
+        &self,
+        _request: tonic::Request<admin::ClearFaultsRequest>,
+    ) -> Result<tonic::Response<admin::ClearFaultsResponse>, Status> {
+        self.clear();
+        Ok(tonic::Response::new(admin::ClearFaultsResponse {}))
+    }
+
+    async fn list_faults(
+        &self,
+        _request: tonic::Request<admin::ListFaultsRequest>,
+    ) -> Result<tonic::Response<admin::ListFaultsResponse>, Status> {
+        let faults = self
+            .list()
+            .into_iter()
+            .map(|(target, fault)| to_proto(target, fault))
+            .collect();
+        Ok(tonic::Response::new(admin::ListFaultsResponse { faults }))
+    }
+}
+
+fn from_proto(fault: admin::Fault) -> Result<(FaultTarget, Fault), Status> {
+    let version = match fault.version.as_str() {
+        "" => None,
+        name => Some(
+            SupportedVersion::from_name(name)
+                .ok_or_else(|| Status::invalid_argument(format!("unknown version {name}")))?,
+        ),
+    };
+    let method = match fault.method.as_str() {
+        "" => None,
+        name => Some(
+            Method::from_name(name)
+                .ok_or_else(|| Status::invalid_argument(format!("unknown method {name}")))?,
+        ),
+    };
+
+    use admin::latency::Distribution;
+    let millis = |millis: u64| match millis <= MAX_LATENCY_MS {
+        true => Ok(Duration::from_millis(millis)),
+        false => Err(Status::invalid_argument(format!(
+            "the latency {millis}ms is above the maximum of {MAX_LATENCY_MS}ms"
+        ))),
+    };
+    let latency = match fault.latency.and_then(|latency| latency.distribution) {
+        None => None,
+        Some(Distribution::FixedMs(latency)) => Some(Latency::Fixed(millis(latency)?)),
+        Some(Distribution::Uniform(uniform)) if uniform.min_ms <= uniform.max_ms => {
+            Some(Latency::Uniform {
+                min: millis(uniform.min_ms)?,
+                max: millis(uniform.max_ms)?,
+            })
+        }
+        Some(Distribution::Uniform(_)) => {
+            return Err(Status::invalid_argument(
+                "a uniform latency's minimum is above its maximum",
+            ))
+        }
+        Some(Distribution::ExponentialMeanMs(mean)) => Some(Latency::Exponential {
+            mean: millis(mean)?,
+        }),
+    };
+
+    let probability = |probability: f64, of: &str| match (0.0..=1.0).contains(&probability) {
+        true => Ok(probability),
+        false => Err(Status::invalid_argument(format!(
+            "the {of} probability {probability} is not between 0 and 1"
+        ))),
+    };
+    let errors = fault
+        .errors
+        .into_iter()
+        .map(|error| match Code::from_i32(error.code) {
+            // codes outside the known range come back as `Unknown`
+            code if code == Code::Ok || code as i32 != error.code => Err(Status::invalid_argument(
+                format!("{} is not an error code", error.code),
+            )),
+            code => Ok((code, probability(error.probability, "error")?)),
+        })
+        .collect::<Result<_, Status>>()?;
+
+    Ok((
+        FaultTarget { version, method },
+        Fault {
+            latency,
+            errors,
+            reset_probability: probability(fault.reset_probability, "reset")?,
+            truncate_probability: probability(fault.truncate_probability, "truncate")?,
+        },
+    ))
+}
+
+fn to_proto(target: FaultTarget, fault: Fault) -> admin::Fault {
+    use admin::latency::Distribution;
+    let latency = fault.latency.map(|latency| admin::Latency {
+        distribution: Some(match latency {
+            Latency::Fixed(latency) => Distribution::FixedMs(latency.as_millis() as u64),
+            Latency::Uniform { min, max } => Distribution::Uniform(admin::UniformLatency {
+                min_ms: min.as_millis() as u64,
+                max_ms: max.as_millis() as u64,
+            }),
+            Latency::Exponential { mean } => {
+                Distribution::ExponentialMeanMs(mean.as_millis() as u64)
+            }
+        }),
+    });
+    admin::Fault {
+        version: target
+            .version
+            .map(|version| version.api_version().name.to_string())
+            .unwrap_or_default(),
+        method: target
+            .method
+            .map(|method| method.name().to_string())
+            .unwrap_or_default(),
+        latency,
+        errors: fault
+            .errors
+            .into_iter()
+            .map(|(code, probability)| admin::ErrorRate {
+                code: code as i32,
+                probability,
+            })
+            .collect(),
+        reset_probability: fault.reset_probability,
+        truncate_probability: fault.truncate_probability,
+    }
+}
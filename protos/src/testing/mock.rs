@@ -0,0 +1,128 @@
+// a `VectorService` without any logic, for testing client code against every version
+
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+use tonic::metadata::MetadataMap;
+use tonic::{async_trait, Request, Response, Status};
+
+use crate::api::inner::{PrintRequest, PrintResponse, SumRequest, SumResponse, VectorService};
+use crate::api_version::{api_version, ApiVersion};
+use crate::auth::{principal, Principal};
+use crate::deadline::{cancellation, deadline, CancellationToken, Deadline};
+
+/// A call the mock received, in the inner service's messages.
+#[derive(Debug, Clone)]
+pub struct Recorded<T> {
+    pub message: T,
+    // the version the call came in through, `None` when the mock is called directly
+    pub version: Option<ApiVersion>,
+    // who made the call, when the server authenticates
+    pub principal: Option<Principal>,
+    // when the caller set a `grpc-timeout`
+    pub deadline: Option<Deadline>,
+    // cancelled once the call is over, answered or not
+    pub cancellation: Option<CancellationToken>,
+    pub metadata: MetadataMap,
+}
+
+#[derive(Debug, Default)]
+struct MockState {
+    prints: VecDeque<Result<PrintResponse, Status>>,
+    sums: VecDeque<Result<SumResponse, Status>>,
+    print_requests: Vec<Recorded<PrintRequest>>,
+    sum_requests: Vec<Recorded<SumRequest>>,
+    latency: Duration,
+}
+
+/// Answers every call with the next scripted response of its method, and records the request.
+///
+/// Clones share the script and the recordings, so a test keeps a clone after mounting the mock with
+/// `add_services_to_server` and serves it as every version. A call without a scripted response fails.
+#[derive(Debug, Clone, Default)]
+pub struct MockVectorService {
+    state: Arc<Mutex<MockState>>,
+}
+
+impl MockVectorService {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push_print(&self, response: Result<PrintResponse, Status>) -> &Self {
+        self.state.lock().unwrap().prints.push_back(response);
+        self
+    }
+
+    pub fn push_sum(&self, response: Result<SumResponse, Status>) -> &Self {
+        self.state.lock().unwrap().sums.push_back(response);
+        self
+    }
+
+    // delays every answer, scripted errors included
+    pub fn set_latency(&self, latency: Duration) -> &Self {
+        self.state.lock().unwrap().latency = latency;
+        self
+    }
+
+    pub fn print_requests(&self) -> Vec<Recorded<PrintRequest>> {
+        self.state.lock().unwrap().print_requests.clone()
+    }
+
+    pub fn sum_requests(&self) -> Vec<Recorded<SumRequest>> {
+        self.state.lock().unwrap().sum_requests.clone()
+    }
+}
+
+fn record<T>(request: Request<T>) -> Recorded<T> {
+    let version = api_version(&request);
+    let principal = principal(&request).cloned();
+    let deadline = deadline(&request);
+    let cancellation = cancellation(&request);
+    let (metadata, _, message) = request.into_parts();
+    Recorded {
+        message,
+        version,
+        principal,
+        deadline,
+        cancellation,
+        metadata,
+    }
+}
+
+fn unscripted(method: &str) -> Status {
+    Status::failed_precondition(format!(
+        "MockVectorService has no scripted {method} response"
+    ))
+}
+
+#[async_trait]
+impl VectorService for MockVectorService {
+    async fn print(
+        &self,
+        request: Request<PrintRequest>,
+    ) -> Result<Response<PrintResponse>, Status> {
+        let (response, latency) = {
+            let mut state = self.state.lock().unwrap();
+            state.print_requests.push(record(request));
+            (state.prints.pop_front(), state.latency)
+        };
+        tokio::time::sleep(latency).await;
+        response
+            .unwrap_or_else(|| Err(unscripted("print")))
+            .map(Response::new)
+    }
+
+    async fn sum(&self, request: Request<SumRequest>) -> Result<Response<SumResponse>, Status> {
+        let (response, latency) = {
+            let mut state = self.state.lock().unwrap();
+            state.sum_requests.push(record(request));
+            (state.sums.pop_front(), state.latency)
+        };
+        tokio::time::sleep(latency).await;
+        response
+            .unwrap_or_else(|| Err(unscripted("sum")))
+            .map(Response::new)
+    }
+}
@@ -0,0 +1,197 @@
+// test support. servers for tests: the same router a real deployment serves, see `serving`,
+// but on a port picked by the OS or over in-memory pipes, ready as soon as `start` returns.
+// `compatibility_matrix` and `strategies` build on them and on the conversions, `MockVectorService` stands in
+// for the inner service.
+
+mod mock;
+pub mod strategies;
+
+use std::future::{ready, Ready};
+use std::io;
+use std::net::SocketAddr;
+use std::task::{Context, Poll};
+
+use futures_util::stream;
+use tokio::io::DuplexStream;
+use tokio::net::TcpListener;
+use tokio::sync::{mpsc, oneshot};
+use tokio::task::JoinHandle;
+use tonic::body::BoxBody;
+use tonic::codegen::{http, Service, StdError};
+use tonic::transport::server::{Router, Routes, TcpIncoming};
+use tonic::transport::{Body, Channel, Endpoint, Uri};
+use tower_layer::Layer;
+
+use crate::serving::{self, ServeOptions};
+pub use crate::wrappers::compatibility_matrix;
+use crate::wrappers::vector_service_client::{SupportedVersion, VectorServiceClient};
+use crate::wrappers::vector_service_server::{VectorService, VersionOverrides};
+pub use mock::{MockVectorService, Recorded};
+
+// size of each direction of an in-memory connection
+const DUPLEX_BUFFER: usize = 64 * 1024;
+
+/// A server running in the background of a test, shut down gracefully when dropped.
+#[derive(Debug)]
+pub struct TestServer {
+    // `None` for in-memory servers
+    local_addr: Option<SocketAddr>,
+    channel: Channel,
+    shutdown: Option<oneshot::Sender<()>>,
+    handle: JoinHandle<Result<(), tonic::transport::Error>>,
+}
+
+impl TestServer {
+    /// Serves on an OS-assigned port of the loopback interface.
+    pub async fn start<T>(service: T, overrides: VersionOverrides) -> Result<Self, StdError>
+    where
+        T: VectorService + Send + Sync,
+    {
+        Self::start_with(service, overrides, ServeOptions::default()).await
+    }
+
+    /// Like [`TestServer::start`], behind the middleware `options` turns on, as a deployment serves it.
+    pub async fn start_with<T>(
+        service: T,
+        overrides: VersionOverrides,
+        options: ServeOptions,
+    ) -> Result<Self, StdError>
+    where
+        T: VectorService + Send + Sync,
+    {
+        Self::start_router(serving::router(service, overrides, options).await).await
+    }
+
+    /// Serves over in-memory pipes, without touching the network.
+    pub async fn start_in_memory<T>(
+        service: T,
+        overrides: VersionOverrides,
+    ) -> Result<Self, StdError>
+    where
+        T: VectorService + Send + Sync,
+    {
+        Self::start_in_memory_with(service, overrides, ServeOptions::default()).await
+    }
+
+    pub async fn start_in_memory_with<T>(
+        service: T,
+        overrides: VersionOverrides,
+        options: ServeOptions,
+    ) -> Result<Self, StdError>
+    where
+        T: VectorService + Send + Sync,
+    {
+        Self::start_router_in_memory(serving::router(service, overrides, options).await).await
+    }
+
+    /// Serves a router built elsewhere, e.g. by `serving::router_with_layer`, on an OS-assigned port.
+    pub async fn start_router<L>(router: Router<L>) -> Result<Self, StdError>
+    where
+        L: Layer<Routes> + Send + 'static,
+        L::Service: Service<http::Request<Body>, Response = http::Response<BoxBody>>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<http::Request<Body>>>::Future: Send + 'static,
+        <L::Service as Service<http::Request<Body>>>::Error: Into<StdError> + Send,
+    {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let local_addr = listener.local_addr()?;
+        let incoming = TcpIncoming::from_listener(listener, true, None)?;
+        let (shutdown, signal) = oneshot::channel();
+        let handle = tokio::spawn(router.serve_with_incoming_shutdown(incoming, async {
+            let _ = signal.await;
+        }));
+        // the listener is bound already, connecting doesn't race the server task
+        let channel = Endpoint::from_shared(format!("http://{local_addr}"))?
+            .connect()
+            .await?;
+        Ok(Self {
+            local_addr: Some(local_addr),
+            channel,
+            shutdown: Some(shutdown),
+            handle,
+        })
+    }
+
+    pub async fn start_router_in_memory<L>(router: Router<L>) -> Result<Self, StdError>
+    where
+        L: Layer<Routes> + Send + 'static,
+        L::Service: Service<http::Request<Body>, Response = http::Response<BoxBody>>
+            + Clone
+            + Send
+            + 'static,
+        <L::Service as Service<http::Request<Body>>>::Future: Send + 'static,
+        <L::Service as Service<http::Request<Body>>>::Error: Into<StdError> + Send,
+    {
+        let (connector, mut connections) = mpsc::unbounded_channel();
+        let incoming = stream::poll_fn(move |cx| {
+            connections
+                .poll_recv(cx)
+                .map(|connection| connection.map(Ok::<_, io::Error>))
+        });
+        let (shutdown, signal) = oneshot::channel();
+        let handle = tokio::spawn(router.serve_with_incoming_shutdown(incoming, async {
+            let _ = signal.await;
+        }));
+        // the address is never dialed, every connection is a new pipe handed to the server
+        let channel = Endpoint::from_static("http://in-memory.test")
+            .connect_with_connector(DuplexConnector(connector))
+            .await?;
+        Ok(Self {
+            local_addr: None,
+            channel,
+            shutdown: Some(shutdown),
+            handle,
+        })
+    }
+
+    pub fn local_addr(&self) -> Option<SocketAddr> {
+        self.local_addr
+    }
+
+    pub fn channel(&self) -> Channel {
+        self.channel.clone()
+    }
+
+    pub fn client(&self, version: SupportedVersion) -> VectorServiceClient<Channel> {
+        VectorServiceClient::new_versioned(self.channel(), version)
+    }
+
+    /// Stops accepting calls and waits for the ones in flight, clients must be dropped first.
+    pub async fn shutdown(mut self) -> Result<(), StdError> {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+        Ok((&mut self.handle).await??)
+    }
+}
+
+impl Drop for TestServer {
+    fn drop(&mut self) {
+        if let Some(shutdown) = self.shutdown.take() {
+            let _ = shutdown.send(());
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+struct DuplexConnector(mpsc::UnboundedSender<DuplexStream>);
+
+impl Service<Uri> for DuplexConnector {
+    type Response = DuplexStream;
+    type Error = io::Error;
+    type Future = Ready<Result<DuplexStream, io::Error>>;
+
+    fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn call(&mut self, _uri: Uri) -> Self::Future {
+        let (client, server) = tokio::io::duplex(DUPLEX_BUFFER);
+        let connected = self.0.send(server).map(|_| client).map_err(|_| {
+            io::Error::new(io::ErrorKind::ConnectionRefused, "the test server stopped")
+        });
+        ready(connected)
+    }
+}
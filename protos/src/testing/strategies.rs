@@ -0,0 +1,54 @@
+// proptest strategies for the messages of every version. the inner service shares V2's messages.
+
+use proptest::collection::vec;
+use proptest::num::f32;
+use proptest::prelude::*;
+
+// every float but NaN, which never equals itself and would fail any round trip comparison
+fn value() -> impl Strategy<Value = f32> {
+    f32::NORMAL | f32::SUBNORMAL | f32::ZERO | f32::INFINITE
+}
+
+pub mod v1 {
+    use proptest::prelude::*;
+
+    use super::{value, vec};
+    use crate::api::v1::{PrintRequest, PrintResponse, SumRequest, SumResponse, Vector};
+
+    pub fn vector() -> impl Strategy<Value = Vector> {
+        ("[a-z0-9]{0,8}", vec(value(), 0..8)).prop_map(|(id, values)| Vector { id, values })
+    }
+
+    pub fn print_request() -> impl Strategy<Value = PrintRequest> {
+        proptest::option::of(vector()).prop_map(|vector| PrintRequest { vector })
+    }
+
+    pub fn print_response() -> impl Strategy<Value = PrintResponse> {
+        any::<u32>().prop_map(|printed_count| PrintResponse { printed_count })
+    }
+
+    pub fn sum_request() -> impl Strategy<Value = SumRequest> {
+        proptest::option::of(vector()).prop_map(|vector| SumRequest { vector })
+    }
+
+    pub fn sum_response() -> impl Strategy<Value = SumResponse> {
+        value().prop_map(|sum| SumResponse { sum })
+    }
+}
+
+pub mod v2 {
+    use proptest::prelude::*;
+
+    use super::{value, vec};
+    use crate::api::v2::{SumRequest, SumResponse};
+
+    pub use super::v1::{print_request, print_response, vector};
+
+    pub fn sum_request() -> impl Strategy<Value = SumRequest> {
+        vec(vector(), 0..4).prop_map(|vectors| SumRequest { vectors })
+    }
+
+    pub fn sum_response() -> impl Strategy<Value = SumResponse> {
+        vec(value(), 0..4).prop_map(|sum| SumResponse { sum })
+    }
+}
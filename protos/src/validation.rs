@@ -0,0 +1,146 @@
+use regex::Regex;
+use tonic::{Request, Response, Status};
+
+use crate::api::inner::{
+    PrintRequest, PrintResponse, SumRequest, SumResponse, Vector, VectorService,
+};
+use crate::errors::{FieldViolation, SumFields, VectorError};
+
+/// What a [`Validated`] service does with NaN and infinite values.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NonFinitePolicy {
+    // summed like any other value
+    #[default]
+    Propagate,
+    // summed as zero
+    Ignore,
+    Reject,
+}
+
+/// Limits on the requests a [`Validated`] service lets through, none by default.
+#[derive(Debug, Clone, Default)]
+pub struct ValidationConfig {
+    pub max_vectors: Option<usize>,
+    pub max_dimension: Option<usize>,
+    // every vector must have exactly this many values
+    pub dimension: Option<usize>,
+    pub max_id_length: Option<usize>,
+    // anchor it to match whole ids, e.g. `^[a-z0-9-]+$`
+    pub id_pattern: Option<Regex>,
+    pub non_finite: NonFinitePolicy,
+}
+
+impl ValidationConfig {
+    /// The violations of a sum request, with the field paths of the inner request. The version
+    /// adapters translate them to those of the caller's version.
+    pub fn check_sum(&self, request: &SumRequest) -> Vec<FieldViolation> {
+        let fields = SumFields::INNER;
+        let mut violations = Vec::new();
+        let count = request.vectors.len();
+        if let Some(max) = self.max_vectors.filter(|&max| count > max) {
+            violations.push(FieldViolation::new(
+                fields.vectors,
+                format!("must have at most {max} vectors, not {count}"),
+            ));
+        }
+        for (index, vector) in request.vectors.iter().enumerate() {
+            self.check_vector(vector, &fields.vector(index), &mut violations);
+        }
+        violations
+    }
+
+    /// The violations of a print request. All versions name its vector `vector`.
+    pub fn check_print(&self, request: &PrintRequest) -> Vec<FieldViolation> {
+        let mut violations = Vec::new();
+        if let Some(vector) = &request.vector {
+            self.check_vector(vector, "vector", &mut violations);
+        }
+        violations
+    }
+
+    fn check_vector(&self, vector: &Vector, path: &str, violations: &mut Vec<FieldViolation>) {
+        let length = vector.id.chars().count();
+        if let Some(max) = self.max_id_length.filter(|&max| length > max) {
+            violations.push(FieldViolation::new(
+                format!("{path}.id"),
+                format!("must be at most {max} characters long, not {length}"),
+            ));
+        }
+        if let Some(pattern) = self.id_pattern.as_ref() {
+            if !pattern.is_match(&vector.id) {
+                violations.push(FieldViolation::new(
+                    format!("{path}.id"),
+                    format!("must match {pattern}"),
+                ));
+            }
+        }
+        let dimension = vector.values.len();
+        match (self.dimension, self.max_dimension) {
+            (Some(required), _) if dimension != required => {
+                violations.push(FieldViolation::new(
+                    format!("{path}.values"),
+                    format!("must have {required} values, not {dimension}"),
+                ));
+            }
+            (_, Some(max)) if dimension > max => {
+                violations.push(FieldViolation::new(
+                    format!("{path}.values"),
+                    format!("must have at most {max} values, not {dimension}"),
+                ));
+            }
+            _ => {}
+        }
+        // the first one is enough, a vector of NaNs shouldn't make a huge error
+        let non_finite = vector.values.iter().position(|value| !value.is_finite());
+        if let (NonFinitePolicy::Reject, Some(position)) = (self.non_finite, non_finite) {
+            violations.push(FieldViolation::new(
+                format!("{path}.values[{position}]"),
+                format!("must be finite, not {}", vector.values[position]),
+            ));
+        }
+    }
+}
+
+/// An inner `VectorService` that rejects the requests `config` doesn't allow before `inner` sees them.
+///
+/// Violations come back as a `VectorError::InvalidArgument`, naming the fields of the caller's version
+/// once they went through the version adapters.
+pub struct Validated<S> {
+    inner: S,
+    config: ValidationConfig,
+}
+
+impl<S: VectorService> Validated<S> {
+    pub fn new(inner: S, config: ValidationConfig) -> Self {
+        Self { inner, config }
+    }
+}
+
+#[tonic::async_trait]
+impl<S: VectorService> VectorService for Validated<S> {
+    async fn print(
+        &self,
+        request: Request<PrintRequest>,
+    ) -> Result<Response<PrintResponse>, Status> {
+        let violations = self.config.check_print(request.get_ref());
+        if !violations.is_empty() {
+            return Err(VectorError::invalid_argument(violations).into());
+        }
+        self.inner.print(request).await
+    }
+
+    async fn sum(&self, mut request: Request<SumRequest>) -> Result<Response<SumResponse>, Status> {
+        let violations = self.config.check_sum(request.get_ref());
+        if !violations.is_empty() {
+            return Err(VectorError::invalid_argument(violations).into());
+        }
+        if self.config.non_finite == NonFinitePolicy::Ignore {
+            for vector in &mut request.get_mut().vectors {
+                for value in vector.values.iter_mut().filter(|value| !value.is_finite()) {
+                    *value = 0.;
+                }
+            }
+        }
+        self.inner.sum(request).await
+    }
+}
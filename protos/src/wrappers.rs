@@ -1,20 +1,32 @@
+// every version with the methods it has, as `(method, request, response)`
 macro_rules! add_versions {
-    ($(($version:ident, $variant:ident)),*) => {
+    ($(($version:ident, $variant:ident, [$($methods:tt),*])),*) => {
         use tonic::{async_trait, Request, Response, Status};
         use crate::api::{inner, $($version,)*};
+        use crate::api_version::tag_api_version;
+        use crate::api_versions;
+        use crate::conversions::{kinds, FromInner, ToInner};
+        use crate::deadline::CallScope;
+        use crate::errors::rewrite_field_paths;
+        use crate::overrides::{BoxFuture, Next};
         $(
-        impl_vector_service!($version);
+        impl_vector_service!($version, $variant);
         )*
         define_server!($($version),*);
         define_client!($(($version, $variant)),*);
+        define_typed_client!($(($version, $variant, [$($methods),*])),*);
+        #[cfg(feature = "testing")]
+        define_compatibility_matrix!($(($version, $variant)),*);
     };
 }
 
-// implements a versions VectorService to just use the inner VectorService instead
+// implements a versions VectorService to just use the inner VectorService instead,
+// unless an override was registered for that version and method
 macro_rules! impl_vector_service {
-    ($version:ident) => {
+    ($version:ident, $variant:ident) => {
         #[async_trait]
-        impl<T> $version::vector_service_server::VectorService for T
+        impl<T> $version::vector_service_server::VectorService
+            for vector_service_server::VersionAdapter<T>
         where
             T: inner::VectorService,
         {
@@ -22,53 +34,83 @@ macro_rules! impl_vector_service {
                 &self,
                 request: Request<$version::PrintRequest>,
             ) -> Result<Response<$version::PrintResponse>, Status> {
-                let tmp = $version::VERSION_NAME;
-                println!("rerouting print from {tmp:?}");
-                let (metadata, extensions, inner_request) = request.into_parts();
-                println!("original request recived in server: {inner_request:?}");
-                let inner_request = inner::PrintRequest::from(inner_request);
-                let request = Request::from_parts(metadata, extensions, inner_request);
-                let (metadata, response, extensions) = inner::VectorService::print(self, request)
-                    .await?
-                    .into_parts();
-
-                Ok(Response::from_parts(
-                    metadata,
-                    $version::PrintResponse::from(response),
-                    extensions,
-                ))
+                reroute_call!(self, request, $version, $variant, print, PrintRequest, PrintResponse, |status| status)
             }
 
             async fn sum(
                 &self,
                 request: Request<$version::SumRequest>,
             ) -> Result<Response<$version::SumResponse>, Status> {
-                let tmp = $version::VERSION_NAME;
-                println!("rerouting sum from {tmp:?}");
+                // the inner service names the fields of its own request in BadRequest details
+                reroute_call!(self, request, $version, $variant, sum, SumRequest, SumResponse, |status| {
+                    rewrite_field_paths(status, |field| $version::SUM_FIELDS.from_inner(field))
+                })
+            }
+        }
+    };
+}
+
+macro_rules! reroute_call {
+    ($self:ident, $request:ident, $version:ident, $variant:ident, $function:ident, $request_type:ident, $response_type:ident, $rewrite_error:expr) => {{
+        let tmp = $version::VERSION_NAME;
+        println!("rerouting {} from {tmp:?}", stringify!($function));
+        let mut request = $request;
+        request.extensions_mut().insert($version::API_VERSION);
+        let scope = CallScope::enter(&mut request);
+
+        let default = |request: Request<$version::$request_type>| -> BoxFuture<'_, _> {
+            Box::pin(async move {
                 let (metadata, extensions, inner_request) = request.into_parts();
                 println!("original request recived in server: {inner_request:?}");
-                let inner_request = inner::SumRequest::from(inner_request);
+                let inner_request = <api_versions::$variant as ToInner<kinds::$request_type>>::to_inner(inner_request);
                 let request = Request::from_parts(metadata, extensions, inner_request);
                 let (metadata, response, extensions) =
-                    inner::VectorService::sum(self, request).await?.into_parts();
+                    inner::VectorService::$function($self.inner.as_ref(), request)
+                        .await
+                        .map_err($rewrite_error)?
+                        .into_parts();
 
-                Ok(Response::from_parts(
-                    metadata,
-                    $version::SumResponse::from(response),
-                    extensions,
-                ))
-            }
-        }
-    };
+                // the inner service answered something this version can't express
+                let response = <api_versions::$variant as FromInner<kinds::$response_type>>::from_inner(response)
+                    .map_err(|e| Status::internal(e.to_string()))?;
+                Ok(Response::from_parts(metadata, response, extensions))
+            })
+        };
+        let result = scope
+            .run(async {
+                match &$self.overrides.$version.$function {
+                    Some(handler) => handler.call(request, Next::new(default)).await,
+                    None => default(request).await,
+                }
+            })
+            .await;
+        tag_api_version(result, $version::API_VERSION)
+    }};
 }
 
 macro_rules! define_client {
     ($(($version:ident, $variant:ident)),*) => {
         pub mod vector_service_client {
             use tonic::codegen::*;
+            use tonic::codegen::http::Uri;
             use tonic::{Request, Response};
+            use tower_layer::Layer;
 
             use crate::api::{self, inner, $($version,)*};
+            use crate::api_versions;
+            use crate::conversions::{kinds, FromInner, ToInner};
+            use crate::fan_out::fan_out_sum;
+            pub use crate::api_version::ApiVersion;
+            pub use crate::balance::{
+                BalanceConfig, BalanceStrategy, BalancedChannel, DnsResolver, Resolver, StaticResolver,
+            };
+            pub use crate::batch::{BatchConfig, BatchingClient};
+            pub use crate::capabilities::{Capabilities, Feature, Method};
+            pub use crate::errors::VectorError;
+            pub use crate::fan_out::{FanOutError, DEFAULT_FAN_OUT_CONCURRENCY};
+            pub use crate::policy::{
+                CallPolicy, HedgingPolicy, PolicyClient, RetryBudget, RetryPolicy,
+            };
 
             #[derive(Debug, Clone)]
             pub enum VectorServiceClient<T> {
@@ -77,12 +119,83 @@ macro_rules! define_client {
                 )*
             }
 
+            #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
             pub enum SupportedVersion {
                 $(
                     $variant,
                 )*
             }
 
+            impl SupportedVersion {
+                pub const fn all() -> &'static [SupportedVersion] {
+                    &[$(SupportedVersion::$variant,)*]
+                }
+
+                // by `ApiVersion::name`, e.g. "V1"
+                pub fn from_name(name: &str) -> Option<Self> {
+                    Self::all()
+                        .iter()
+                        .copied()
+                        .find(|version| version.api_version().name == name)
+                }
+
+                pub fn api_version(self) -> ApiVersion {
+                    match self {
+                        $(
+                            SupportedVersion::$variant => $version::API_VERSION,
+                        )*
+                    }
+                }
+
+                // the gRPC service name, also what balanced clients health check for
+                pub fn service_name(self) -> &'static str {
+                    match self {
+                        $(
+                            SupportedVersion::$variant => $version::SERVICE_NAME,
+                        )*
+                    }
+                }
+
+                pub fn capabilities(self) -> Capabilities {
+                    match self {
+                        $(
+                            SupportedVersion::$variant => $version::CAPABILITIES,
+                        )*
+                    }
+                }
+
+                pub fn supports(self, method: Method, feature: Feature) -> bool {
+                    self.capabilities().supports(method, feature)
+                }
+
+                // where the vectors of a sum request are, for the field paths of `BadRequest` errors
+                pub fn sum_fields(self) -> crate::errors::SumFields {
+                    match self {
+                        $(
+                            SupportedVersion::$variant => $version::SUM_FIELDS,
+                        )*
+                    }
+                }
+            }
+
+            impl<T> VectorServiceClient<T> {
+                pub fn version(&self) -> SupportedVersion {
+                    match self {
+                        $(
+                            VectorServiceClient::$variant(_) => SupportedVersion::$variant,
+                        )*
+                    }
+                }
+
+                pub fn capabilities(&self) -> Capabilities {
+                    self.version().capabilities()
+                }
+
+                pub fn supports(&self, method: Method, feature: Feature) -> bool {
+                    self.version().supports(method, feature)
+                }
+            }
+
             impl VectorServiceClient<tonic::transport::Channel> {
             pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
                 where
@@ -102,6 +215,17 @@ macro_rules! define_client {
                     Ok(Self::new_versioned(conn, version))
                 }
             }
+            impl VectorServiceClient<BalancedChannel> {
+                /// Balances calls across the endpoints `resolver` returns, skipping those that don't serve `version`.
+                pub async fn connect_balanced(
+                    resolver: impl Resolver,
+                    config: BalanceConfig,
+                    version: SupportedVersion,
+                ) -> Result<Self, StdError> {
+                    let channel = BalancedChannel::new(resolver, version.service_name(), config).await?;
+                    Ok(Self::new_versioned(channel, version))
+                }
+            }
             impl<T> VectorServiceClient<T>
             where
                 T: tonic::client::GrpcService<tonic::body::BoxBody>,
@@ -125,6 +249,7 @@ macro_rules! define_client {
                     }
                 }
 
+                #[deprecated(note = "always speaks V1, use `with_interceptor_versioned` to pick the version")]
                 pub fn with_interceptor<F>(
                     inner: T,
                     interceptor: F,
@@ -141,10 +266,62 @@ macro_rules! define_client {
                     <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
                         Into<StdError> + Send + Sync,
                 {
-
                     VectorServiceClient::new(InterceptedService::new(inner, interceptor))
                 }
 
+                pub fn with_interceptor_versioned<F>(
+                    inner: T,
+                    interceptor: F,
+                    version: SupportedVersion,
+                ) -> VectorServiceClient<InterceptedService<T, F>>
+                where
+                    F: tonic::service::Interceptor,
+                    T::ResponseBody: Default,
+                    T: tonic::codegen::Service<
+                        http::Request<tonic::body::BoxBody>,
+                        Response = http::Response<
+                            <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                        >,
+                    >,
+                    <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                        Into<StdError> + Send + Sync,
+                {
+                    VectorServiceClient::new_versioned(InterceptedService::new(inner, interceptor), version)
+                }
+
+                pub fn with_origin(inner: T, origin: Uri) -> Self {
+                    Self::with_origin_versioned(inner, origin, SupportedVersion::V1)
+                }
+
+                pub fn with_origin_versioned(inner: T, origin: Uri, version: SupportedVersion) -> Self {
+                    match version {
+                        $(
+                            SupportedVersion::$variant => {
+                                let client = api::$version::vector_service_client::VectorServiceClient::with_origin(inner, origin);
+                                Self::$variant(client)
+                            }
+                        )*
+                    }
+                }
+
+                // wraps the transport in any tower layer (auth, timeouts, retries...) before picking the version
+                pub fn with_layer_versioned<L>(
+                    inner: T,
+                    layer: L,
+                    version: SupportedVersion,
+                ) -> VectorServiceClient<L::Service>
+                where
+                    L: Layer<T>,
+                    L::Service: tonic::client::GrpcService<tonic::body::BoxBody>,
+                    <L::Service as tonic::client::GrpcService<tonic::body::BoxBody>>::Error: Into<StdError>,
+                    <L::Service as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody:
+                        Body<Data = Bytes> + Send + 'static,
+                    <<L::Service as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody as Body>::Error:
+                        Into<StdError> + Send,
+                {
+                    VectorServiceClient::new_versioned(layer.layer(inner), version)
+                }
+
                 #[must_use]
                 pub fn send_compressed(self, encoding: CompressionEncoding) -> Self {
                     call_variant_method!(self, send_compressed, encoding, $($variant),*)
@@ -166,8 +343,167 @@ macro_rules! define_client {
                 }
 
                 delegate_client_call!(print, PrintRequest, PrintResponse, $(($version ,$variant)),*);
-                delegate_client_call!(sum, SumRequest, SumResponse, $(($version ,$variant)),*);
+                delegate_sum_call!($(($version ,$variant)),*);
+
+                // like `print`, with the error's details decoded
+                pub async fn try_print(
+                    &mut self,
+                    request: impl tonic::IntoRequest<inner::PrintRequest>,
+                ) -> Result<Response<inner::PrintResponse>, VectorError> {
+                    self.print(request).await.map_err(VectorError::from)
+                }
+
+                // like `sum`, with the error's details decoded
+                pub async fn try_sum(
+                    &mut self,
+                    request: impl tonic::IntoRequest<inner::SumRequest>,
+                ) -> Result<Response<inner::SumResponse>, VectorError>
+                where
+                    T: Clone,
+                {
+                    self.sum(request).await.map_err(VectorError::from)
+                }
+            }
+        }
+    };
+}
+
+// a client whose version is picked at compile time, e.g. `VectorServiceClient<api_versions::V1>`.
+// requests and responses are the version's own types, and only the methods the version has exist.
+// use the enum client from `vector_service_client` when the version is only known at runtime.
+macro_rules! define_typed_client {
+    ($(($version:ident, $variant:ident, [$(($method:ident, $request:ident, $response:ident)),*])),*) => {
+        pub mod typed_vector_service_client {
+            use std::fmt;
+
+            use tonic::codegen::*;
+            use tonic::codegen::http::Uri;
+            use tonic::Response;
+
+            use crate::api::{self, $($version,)*};
+            pub use crate::api_versions;
+
+            /// Associates a version marker with the client generated for that version.
+            pub trait ClientVersion: crate::conversions::Version {
+                type Client<T>;
+            }
+
+            pub struct VectorServiceClient<V: ClientVersion, T = tonic::transport::Channel> {
+                inner: V::Client<T>,
+            }
+
+            impl<V: ClientVersion, T> Clone for VectorServiceClient<V, T>
+            where
+                V::Client<T>: Clone,
+            {
+                fn clone(&self) -> Self {
+                    Self {
+                        inner: self.inner.clone(),
+                    }
+                }
+            }
+
+            impl<V: ClientVersion, T> fmt::Debug for VectorServiceClient<V, T>
+            where
+                V::Client<T>: fmt::Debug,
+            {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    f.debug_struct("VectorServiceClient")
+                        .field("inner", &self.inner)
+                        .finish()
+                }
+            }
+
+            impl<V: ClientVersion, T> VectorServiceClient<V, T> {
+                pub fn into_inner(self) -> V::Client<T> {
+                    self.inner
+                }
+            }
+
+            $(
+            impl ClientVersion for api_versions::$variant {
+                type Client<T> = api::$version::vector_service_client::VectorServiceClient<T>;
+            }
+
+            impl VectorServiceClient<api_versions::$variant, tonic::transport::Channel> {
+                pub async fn connect<D>(dst: D) -> Result<Self, tonic::transport::Error>
+                where
+                    D: TryInto<tonic::transport::Endpoint>,
+                    D::Error: Into<StdError>,
+                {
+                    let conn = tonic::transport::Endpoint::new(dst)?.connect().await?;
+                    Ok(Self::new(conn))
+                }
+            }
+
+            impl<T> VectorServiceClient<api_versions::$variant, T>
+            where
+                T: tonic::client::GrpcService<tonic::body::BoxBody>,
+                T::Error: Into<StdError>,
+                T::ResponseBody: Body<Data = Bytes> + Send + 'static,
+                <T::ResponseBody as Body>::Error: Into<StdError> + Send,
+            {
+                pub fn new(inner: T) -> Self {
+                    Self {
+                        inner: api::$version::vector_service_client::VectorServiceClient::new(inner),
+                    }
+                }
+
+                pub fn with_origin(inner: T, origin: Uri) -> Self {
+                    Self {
+                        inner: api::$version::vector_service_client::VectorServiceClient::with_origin(inner, origin),
+                    }
+                }
+
+                pub fn with_interceptor<F>(
+                    inner: T,
+                    interceptor: F,
+                ) -> VectorServiceClient<api_versions::$variant, InterceptedService<T, F>>
+                where
+                    F: tonic::service::Interceptor,
+                    T::ResponseBody: Default,
+                    T: tonic::codegen::Service<
+                        http::Request<tonic::body::BoxBody>,
+                        Response = http::Response<
+                            <T as tonic::client::GrpcService<tonic::body::BoxBody>>::ResponseBody,
+                        >,
+                    >,
+                    <T as tonic::codegen::Service<http::Request<tonic::body::BoxBody>>>::Error:
+                        Into<StdError> + Send + Sync,
+                {
+                    VectorServiceClient::<api_versions::$variant, _>::new(InterceptedService::new(inner, interceptor))
+                }
+
+                #[must_use]
+                pub fn send_compressed(self, encoding: CompressionEncoding) -> Self {
+                    Self { inner: self.inner.send_compressed(encoding) }
+                }
+
+                #[must_use]
+                pub fn accept_compressed(self, encoding: CompressionEncoding) -> Self {
+                    Self { inner: self.inner.accept_compressed(encoding) }
+                }
+
+                #[must_use]
+                pub fn max_decoding_message_size(self, limit: usize) -> Self {
+                    Self { inner: self.inner.max_decoding_message_size(limit) }
+                }
+
+                #[must_use]
+                pub fn max_encoding_message_size(self, limit: usize) -> Self {
+                    Self { inner: self.inner.max_encoding_message_size(limit) }
+                }
+
+                $(
+                pub async fn $method(
+                    &mut self,
+                    request: impl tonic::IntoRequest<$version::$request>,
+                ) -> Result<Response<$version::$response>, tonic::Status> {
+                    self.inner.$method(request).await
+                }
+                )*
             }
+            )*
         }
     };
 }
@@ -179,28 +515,88 @@ macro_rules! delegate_client_call {
             request: impl tonic::IntoRequest<inner::$request_type>,
         ) -> Result<Response<inner::$response_type>, tonic::Status> {
             let request = request.into_request();
-            let (metadata, extensions, inner_request) = request.into_parts();
-            
             match self {
                 $(
                 VectorServiceClient::$variant(client) => {
-                    let inner_request = $version::$request_type::from(inner_request);
-                    println!("request sent from client: {inner_request:?}");
-                    let request = Request::from_parts(metadata, extensions, inner_request);
-                    let (metadata, inner_response, extensions) =
-                        client.$function(request).await?.into_parts();
-                    Ok(Response::from_parts(
-                        metadata,
-                        inner::$response_type::from(inner_response),
-                        extensions,
-                    ))
+                    versioned_client_call!(client, $variant, $function, $request_type, $response_type, request)
                 }
                 )*
+            }
+        }
+    };
+}
 
+// like delegate_client_call, but a multi-vector request sent through a version that can only
+// sum a single vector is split into concurrent single-vector calls instead of failing the conversion
+macro_rules! delegate_sum_call {
+    ($(($version:ident, $variant:ident)),*) => {
+        pub async fn sum(
+            &mut self,
+            request: impl tonic::IntoRequest<inner::SumRequest>,
+        ) -> Result<Response<inner::SumResponse>, tonic::Status>
+        where
+            T: Clone,
+        {
+            let request = request.into_request();
+            let single_vector_only = !self.supports(Method::Sum, Feature::MultiVector);
+            if single_vector_only && request.get_ref().vectors.len() != 1 {
+                return Ok(self.sum_fan_out(request, DEFAULT_FAN_OUT_CONCURRENCY).await?);
+            }
+            match self {
+                $(
+                VectorServiceClient::$variant(client) => {
+                    versioned_client_call!(client, $variant, sum, SumRequest, SumResponse, request)
+                }
+                )*
+            }
+        }
+
+        // sends every vector of the request as its own call, with at most `concurrency` calls in flight
+        pub async fn sum_fan_out(
+            &mut self,
+            request: impl tonic::IntoRequest<inner::SumRequest>,
+            concurrency: usize,
+        ) -> Result<Response<inner::SumResponse>, FanOutError>
+        where
+            T: Clone,
+        {
+            let request = request.into_request();
+            match self {
+                $(
+                VectorServiceClient::$variant(client) => {
+                    fan_out_sum(request, concurrency, $version::SUM_FIELDS, |request| {
+                        let mut client = client.clone();
+                        async move {
+                            versioned_client_call!(client, $variant, sum, SumRequest, SumResponse, request)
+                        }
+                    })
+                    .await
+                }
+                )*
             }
         }
     };
 }
+
+// the inner request converted to the version's, sent through its generated client, and the response
+// converted back. every call of the enum client goes through here, in a fn or block returning `Status` errors
+macro_rules! versioned_client_call {
+    ($client:ident, $variant:ident, $function:ident, $request_type:ident, $response_type:ident, $request:ident) => {{
+        let (metadata, extensions, inner_request) = $request.into_parts();
+        let inner_request = <api_versions::$variant as FromInner<kinds::$request_type>>::from_inner(inner_request)
+            .map_err(|e| tonic::Status::invalid_argument(e.to_string()))?;
+        println!("request sent from client: {inner_request:?}");
+        let request = Request::from_parts(metadata, extensions, inner_request);
+        let (metadata, inner_response, extensions) =
+            $client.$function(request).await?.into_parts();
+        Ok(Response::from_parts(
+            metadata,
+            <api_versions::$variant as ToInner<kinds::$response_type>>::to_inner(inner_response),
+            extensions,
+        ))
+    }};
+}
+
 macro_rules! call_variant_method {
     ($self:ident, $method:ident, $arg:ident, $($variant:ident),*) => {
         match $self {
@@ -219,25 +615,85 @@ macro_rules! define_server {
             // VectorService can also be accessed directly from pinecone_service,
             // but exported here to keep the structure similar to the tonic-generated code
             pub use crate::api::inner::VectorService;
+            pub use crate::overrides::{MethodOverride, Next};
+            pub use crate::shadow::{Divergence, ShadowConfig, ShadowMetrics, Shadowed};
+
+            use crate::concurrency::ConcurrencyLimits;
 
             use std::sync::Arc;
             use tonic::transport::server::Router;
             use tonic::transport::Server;
+            use tonic_health::pb::health_server::{Health, HealthServer};
+
+            /// Per-version, per-method handlers that run instead of (or around) the inner `VectorService`.
+            #[derive(Default, Clone)]
+            pub struct VersionOverrides {
+                $(
+                    pub $version: crate::api::$version::Overrides,
+                )*
+            }
+
+            /// Serves a single inner `VectorService` as every public API version.
+            pub struct VersionAdapter<T> {
+                pub(crate) inner: Arc<T>,
+                pub(crate) overrides: Arc<VersionOverrides>,
+            }
+
+            impl<T> Clone for VersionAdapter<T> {
+                fn clone(&self) -> Self {
+                    Self {
+                        inner: self.inner.clone(),
+                        overrides: self.overrides.clone(),
+                    }
+                }
+            }
+
+            impl<T: VectorService> VersionAdapter<T> {
+                pub fn new(service: T, overrides: VersionOverrides) -> Self {
+                    Self {
+                        inner: Arc::new(service),
+                        overrides: Arc::new(overrides),
+                    }
+                }
+            }
 
             pub fn add_services_to_router<T, R>(service: T) -> impl FnOnce(Router<R>) -> Router<R>
             where
                 T: VectorService + Send + Sync,
                 R:  Sized,
             {
-                let service_arc = Arc::new(service);
+                add_services_to_router_with_overrides(service, VersionOverrides::default())
+            }
+
+            pub fn add_services_to_router_with_overrides<T, R>(
+                service: T,
+                overrides: VersionOverrides,
+            ) -> impl FnOnce(Router<R>) -> Router<R>
+            where
+                T: VectorService + Send + Sync,
+                R:  Sized,
+            {
+                let adapter = VersionAdapter::new(service, overrides);
                 move |server| {
                     server
                         $(
-                            .add_service(crate::api::$version::vector_service_server::VectorServiceServer::from_arc(service_arc.clone()))
+                            .add_service(crate::api::$version::vector_service_server::VectorServiceServer::new(adapter.clone()))
                         )*
                 }
             }
 
+            /// The standard gRPC health service, reporting every version's service as serving.
+            /// Balanced clients use it to find the endpoints that speak their version.
+            pub async fn health_service() -> HealthServer<impl Health> {
+                let (mut reporter, service) = tonic_health::server::health_reporter();
+                $(
+                    reporter
+                        .set_service_status(crate::api::$version::SERVICE_NAME, tonic_health::ServingStatus::Serving)
+                        .await;
+                )*
+                service
+            }
+
             // this function acts similarly to the previous one, but its ment to add services to a brand new Server which wasn't converted into a Router yet.
             // it is currently used only in pinecone-sim.
             // consider adding a middleware layer to the pinecone-sim services, and use the same function as in the real pinecone.
@@ -246,11 +702,38 @@ macro_rules! define_server {
                 T: VectorService + Send + Sync,
                 R:  Sized + Clone,
             {
-                let service_arc = Arc::new(service);
+                add_services_to_server_with_overrides(service, VersionOverrides::default())
+            }
+
+            pub fn add_services_to_server_with_overrides<T, R>(
+                service: T,
+                overrides: VersionOverrides,
+            ) -> impl FnOnce(Server<R>) -> Router<R>
+            where
+                T: VectorService + Send + Sync,
+                R:  Sized + Clone,
+            {
+                add_limited_services_to_server(service, overrides, ConcurrencyLimits::default())
+            }
+
+            /// Like `add_services_to_server_with_overrides`, with each version's service behind its
+            /// own concurrency limiter. The global limit is a layer of the server, see `ConcurrencyLimits::global_layer`.
+            pub fn add_limited_services_to_server<T, R>(
+                service: T,
+                overrides: VersionOverrides,
+                limits: ConcurrencyLimits,
+            ) -> impl FnOnce(Server<R>) -> Router<R>
+            where
+                T: VectorService + Send + Sync,
+                R:  Sized + Clone,
+            {
+                let adapter = VersionAdapter::new(service, overrides);
                 move |mut server| {
                     server
                         $(
-                            .add_service(crate::api::$version::vector_service_server::VectorServiceServer::from_arc(service_arc.clone()))
+                            .add_service(limits.limit_service(
+                                crate::api::$version::vector_service_server::VectorServiceServer::new(adapter.clone()),
+                            ))
                         )*
                 }
             }
@@ -258,8 +741,296 @@ macro_rules! define_server {
     };
 }
 
+// runs scenarios through every client and served version, see `testing::compatibility_matrix`
+#[cfg(feature = "testing")]
+macro_rules! define_compatibility_matrix {
+    ($(($version:ident, $variant:ident)),*) => {
+        pub mod compatibility_matrix {
+            use std::fmt;
+
+            use prost::Message;
+            use tonic::codegen::StdError;
+            use tonic::transport::server::Router;
+            use tonic::transport::{Channel, Server};
+            use tonic::{Code, Request, Status};
+
+            use super::vector_service_client::{SupportedVersion, VectorServiceClient};
+            use super::vector_service_server::{VectorService, VersionAdapter, VersionOverrides};
+            use crate::api::{self, inner};
+            use crate::api_versions;
+            use crate::capabilities::{Feature, Method};
+            use crate::conversions::{kinds, FromInner, ToInner};
+            use crate::testing::TestServer;
+
+            /// One call of a scenario, in the inner service's messages.
+            #[derive(Debug, Clone, PartialEq)]
+            pub enum Call {
+                Print(inner::PrintRequest),
+                Sum(inner::SumRequest),
+            }
+
+            #[derive(Debug, Clone, PartialEq)]
+            pub enum Reply {
+                Print(inner::PrintResponse),
+                Sum(inner::SumResponse),
+            }
+
+            /// A client a scenario is run through.
+            #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+            pub enum ClientKind {
+                // the client generated for the version, from `actual_clients`
+                Raw(SupportedVersion),
+                // the enum client from `vector_service_client`
+                Versioned(SupportedVersion),
+            }
+
+            impl ClientKind {
+                pub fn all() -> Vec<ClientKind> {
+                    let versions = SupportedVersion::all().iter().copied();
+                    versions
+                        .clone()
+                        .map(ClientKind::Raw)
+                        .chain(versions.map(ClientKind::Versioned))
+                        .collect()
+                }
+
+                // whether every call can be expressed in the client's own messages
+                fn unsupported_call(self, calls: &[Call]) -> Option<String> {
+                    let ClientKind::Raw(version) = self else {
+                        return None;
+                    };
+                    calls.iter().find_map(|call| match call {
+                        Call::Sum(request)
+                            if request.vectors.len() != 1
+                                && !version.supports(Method::Sum, Feature::MultiVector) =>
+                        {
+                            Some(format!("{} vectors in a single {version:?} request", request.vectors.len()))
+                        }
+                        _ => None,
+                    })
+                }
+            }
+
+            impl fmt::Display for ClientKind {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    match self {
+                        ClientKind::Raw(version) => write!(f, "raw {version:?}"),
+                        ClientKind::Versioned(version) => write!(f, "versioned {version:?}"),
+                    }
+                }
+            }
+
+            #[derive(Debug, Clone, PartialEq)]
+            pub enum Outcome {
+                Pass,
+                Fail(String),
+                Unsupported(String),
+            }
+
+            #[derive(Debug, Clone)]
+            pub struct Cell {
+                pub client: ClientKind,
+                // the newest version of the server, which serves every version up to it
+                pub served: SupportedVersion,
+                pub outcome: Outcome,
+            }
+
+            #[derive(Debug, Clone, Default)]
+            pub struct Matrix {
+                pub cells: Vec<Cell>,
+            }
+
+            impl Matrix {
+                pub fn get(&self, client: ClientKind, served: SupportedVersion) -> Option<&Outcome> {
+                    self.cells
+                        .iter()
+                        .find(|cell| cell.client == client && cell.served == served)
+                        .map(|cell| &cell.outcome)
+                }
+
+                pub fn failures(&self) -> impl Iterator<Item = &Cell> {
+                    self.cells
+                        .iter()
+                        .filter(|cell| matches!(cell.outcome, Outcome::Fail(_)))
+                }
+            }
+
+            // one row per client, one column per served version
+            impl fmt::Display for Matrix {
+                fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                    write!(f, "{:<14}", "")?;
+                    for served in SupportedVersion::all() {
+                        write!(f, " | served {:<12}", format!("{served:?}"))?;
+                    }
+                    writeln!(f)?;
+                    for client in ClientKind::all() {
+                        write!(f, "{:<14}", client.to_string())?;
+                        for &served in SupportedVersion::all() {
+                            let outcome = match self.get(client, served) {
+                                Some(Outcome::Pass) => "pass",
+                                Some(Outcome::Fail(_)) => "FAIL",
+                                Some(Outcome::Unsupported(_)) => "unsupported",
+                                None => "-",
+                            };
+                            write!(f, " | {outcome:<19}")?;
+                        }
+                        writeln!(f)?;
+                    }
+                    for cell in self.failures() {
+                        if let Outcome::Fail(reason) = &cell.outcome {
+                            writeln!(f, "{} against {:?}: {reason}", cell.client, cell.served)?;
+                        }
+                    }
+                    Ok(())
+                }
+            }
+
+            /// Runs `calls` through every client against a server of every version, and compares each reply,
+            /// converted back to the inner messages, with what the inner service answers directly.
+            /// Versions added to `add_versions!` join the matrix on their own.
+            pub async fn run<T>(
+                service: T,
+                overrides: VersionOverrides,
+                calls: &[Call],
+            ) -> Result<Matrix, StdError>
+            where
+                T: VectorService + Send + Sync,
+            {
+                let adapter = VersionAdapter::new(service, overrides);
+                let mut expected = Vec::with_capacity(calls.len());
+                for call in calls {
+                    expected.push(call_inner(adapter.inner.as_ref(), call).await);
+                }
+
+                let mut matrix = Matrix::default();
+                for &served in SupportedVersion::all() {
+                    let server = TestServer::start_router_in_memory(router(&adapter, served)).await?;
+                    for client in ClientKind::all() {
+                        let outcome = run_cell(client, server.channel(), calls, &expected).await;
+                        matrix.cells.push(Cell {
+                            client,
+                            served,
+                            outcome,
+                        });
+                    }
+                }
+                Ok(matrix)
+            }
+
+            fn router<T>(adapter: &VersionAdapter<T>, served: SupportedVersion) -> Router
+            where
+                T: VectorService + Send + Sync,
+            {
+                let newest = served.api_version().ordinal;
+                Server::builder()
+                    $(
+                        .add_optional_service(
+                            (api::$version::API_VERSION.ordinal <= newest)
+                                .then(|| api::$version::vector_service_server::VectorServiceServer::new(adapter.clone())),
+                        )
+                    )*
+            }
+
+            async fn run_cell(
+                client: ClientKind,
+                channel: Channel,
+                calls: &[Call],
+                expected: &[Result<Reply, Status>],
+            ) -> Outcome {
+                if let Some(reason) = client.unsupported_call(calls) {
+                    return Outcome::Unsupported(reason);
+                }
+                for (call, expected) in calls.iter().zip(expected) {
+                    let actual = match client {
+                        ClientKind::Raw(version) => call_raw(version, channel.clone(), call).await,
+                        ClientKind::Versioned(version) => {
+                            call_versioned(VectorServiceClient::new_versioned(channel.clone(), version), call).await
+                        }
+                    };
+                    match (actual, expected) {
+                        (Ok(actual), Ok(expected)) if actual == *expected => {}
+                        (Err(actual), Err(expected)) if actual.code() == expected.code() => {}
+                        (Err(status), _) if status.code() == Code::Unimplemented => {
+                            return Outcome::Unsupported(status.message().to_string());
+                        }
+                        (actual, expected) => {
+                            return Outcome::Fail(format!("{call:?}: expected {expected:?}, got {actual:?}"));
+                        }
+                    }
+                }
+                Outcome::Pass
+            }
+
+            async fn call_inner<T: VectorService>(service: &T, call: &Call) -> Result<Reply, Status> {
+                match call {
+                    Call::Print(request) => service
+                        .print(Request::new(request.clone()))
+                        .await
+                        .map(|response| Reply::Print(response.into_inner())),
+                    Call::Sum(request) => service
+                        .sum(Request::new(request.clone()))
+                        .await
+                        .map(|response| Reply::Sum(response.into_inner())),
+                }
+            }
+
+            async fn call_versioned(mut client: VectorServiceClient<Channel>, call: &Call) -> Result<Reply, Status> {
+                match call {
+                    Call::Print(request) => client
+                        .print(request.clone())
+                        .await
+                        .map(|response| Reply::Print(response.into_inner())),
+                    Call::Sum(request) => client
+                        .sum(request.clone())
+                        .await
+                        .map(|response| Reply::Sum(response.into_inner())),
+                }
+            }
+
+            async fn call_raw(version: SupportedVersion, channel: Channel, call: &Call) -> Result<Reply, Status> {
+                match (version, call) {
+                    $(
+                        (SupportedVersion::$variant, Call::Print(request)) => {
+                            raw_call!($version, $variant, channel, request, print, PrintRequest, PrintResponse).map(Reply::Print)
+                        }
+                        (SupportedVersion::$variant, Call::Sum(request)) => {
+                            raw_call!($version, $variant, channel, request, sum, SumRequest, SumResponse).map(Reply::Sum)
+                        }
+                    )*
+                }
+            }
+
+            // `actual_clients` and `api` are generated from the same files, so their messages share the wire format
+            fn transcode<A: Message, B: Message + Default>(message: &A) -> B {
+                B::decode(message.encode_to_vec().as_slice()).expect("both messages have the same descriptor")
+            }
+        }
+    };
+}
+
+// downgrades an inner request to the version, calls its `actual_clients` client and upgrades the reply
+#[cfg(feature = "testing")]
+macro_rules! raw_call {
+    ($version:ident, $variant:ident, $channel:expr, $request:expr, $function:ident, $request_type:ident, $response_type:ident) => {{
+        let request: api::$version::$request_type =
+            <api_versions::$variant as FromInner<kinds::$request_type>>::from_inner($request.clone())
+                .map_err(|e| Status::invalid_argument(e.to_string()))?;
+        let mut client = crate::actual_clients::$version::vector_service_client::VectorServiceClient::new($channel);
+        client
+            .$function(transcode::<_, crate::actual_clients::$version::$request_type>(&request))
+            .await
+            .map(|response| {
+                let response: api::$version::$response_type = transcode(response.get_ref());
+                <api_versions::$variant as ToInner<kinds::$response_type>>::to_inner(response)
+            })
+    }};
+}
+
 // Maybe we can use #[allow(non_camel_case_types)] and get rid of $variant?
 // if we do so, this code will be a bit less readable,
 // but we can have all the changes for adding a new version happen in outer-protos/src/lib.rs
 // and here we can import a list of "user facing apis" and pass it to the macro
-add_versions!((v1, V1), (v2, V2));
+add_versions!(
+    (v1, V1, [(print, PrintRequest, PrintResponse), (sum, SumRequest, SumResponse)]),
+    (v2, V2, [(print, PrintRequest, PrintResponse), (sum, SumRequest, SumResponse)])
+);
@@ -0,0 +1,19 @@
+use anyhow::Context;
+use protos::recording::{read_recording, replay};
+use tonic::transport::Endpoint;
+
+// replays a recording against another server and prints how its responses differ
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let mut args = std::env::args().skip(1);
+    let (Some(recording), Some(target)) = (args.next(), args.next()) else {
+        anyhow::bail!("usage: replay <recording> <target uri>");
+    };
+    let calls = read_recording(&recording).with_context(|| format!("reading {recording}"))?;
+    let channel = Endpoint::from_shared(target)?.connect().await?;
+    println!("replaying {} calls", calls.len());
+    let report = replay(&calls, channel).await;
+    print!("{report}");
+    anyhow::ensure!(report.is_clean(), "the replayed responses differ");
+    Ok(())
+}
@@ -0,0 +1,15 @@
+use protos::simulator::Faults;
+use versioning_grpc::{serve_simulator, ServeOptions, VectorHandler};
+
+// starts without faults, they are set through the FaultAdmin service
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let port = std::env::args()
+        .nth(1)
+        .map_or(Ok(1620), |port| port.parse())?;
+    let inner_service = VectorHandler {
+        name: "simulator".to_string(),
+    };
+    println!("simulating on port {port}");
+    serve_simulator(port, inner_service, Faults::new(), ServeOptions::default()).await
+}
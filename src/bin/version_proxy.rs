@@ -0,0 +1,11 @@
+use versioning_grpc::proxy::{serve_proxy, ProxyConfig};
+
+#[tokio::main]
+async fn main() -> anyhow::Result<()> {
+    let config_path = std::env::args()
+        .nth(1)
+        .unwrap_or_else(|| "proxy/v1_to_v2.toml".to_string());
+    let config = ProxyConfig::from_file(config_path)?;
+    println!("proxying to {} on {}", config.upstream, config.listen);
+    serve_proxy(config).await
+}
@@ -1,22 +1,142 @@
+// handlers answer with tonic::Status like the generated services they implement
+#![allow(clippy::result_large_err)]
+
+use std::future::Future;
+use std::sync::Arc;
+
 use anyhow::Context;
 
-use protos::vector_service::vector_service_server;
-use protos::vector_service::{PrintRequest, PrintResponse, SumRequest, SumResponse, VectorService};
+use protos::api::v1;
+use protos::auth::principal;
+use protos::deadline::{cancellation, deadline, CancellationToken, Deadline};
+use protos::recording::{Recorder, RecorderLayer};
+use protos::serving::{self, ServeLayers};
+use protos::simulator::{FaultLayer, Faults};
+use protos::vector_service::vector_service_server::{
+    MethodOverride, Next, ShadowConfig, ShadowMetrics, Shadowed, VersionOverrides,
+};
+use protos::vector_service::{
+    api_version, PrintRequest, PrintResponse, SumRequest, SumResponse, Vector, VectorService,
+};
 
 use tonic::async_trait;
-use tonic::transport::Server;
+use tonic::transport::server::Router;
 use tonic::{Request, Response};
 
-pub async fn serve(port: u16, inner_service: VectorHandler) -> anyhow::Result<()> {
+pub mod proxy;
+
+pub use protos::serving::ServeOptions;
+
+/// Serves `inner_service` as every API version.
+pub async fn serve<T: VectorService>(
+    port: u16,
+    inner_service: T,
+    options: ServeOptions,
+) -> anyhow::Result<()> {
+    let bind_addr = format!("0.0.0.0:{}", port).parse()?;
+
+    serving::router(inner_service, overrides(), options)
+        .await
+        .serve(bind_addr)
+        .await
+        .context("error initializing server")
+}
+
+/// Serves like [`serve`], while every call is also sent to `shadow` and its answers compared
+/// with `inner_service`'s, which alone are returned. The comparisons are counted in the metrics
+/// returned along with the server, which runs once awaited.
+pub fn serve_shadowed<T, S>(
+    port: u16,
+    inner_service: T,
+    shadow: S,
+    config: ShadowConfig,
+    options: ServeOptions,
+) -> (Arc<ShadowMetrics>, impl Future<Output = anyhow::Result<()>>)
+where
+    T: VectorService,
+    S: VectorService,
+{
+    let shadowed = Shadowed::new(inner_service, shadow, config);
+    (shadowed.metrics(), serve(port, shadowed, options))
+}
+
+/// Serves like [`serve`], appending every versioned call to the `recorder`'s recording.
+pub async fn serve_recorded<T: VectorService>(
+    port: u16,
+    inner_service: T,
+    recorder: Recorder,
+    options: ServeOptions,
+) -> anyhow::Result<()> {
     let bind_addr = format!("0.0.0.0:{}", port).parse()?;
 
-    let add_services = vector_service_server::add_services_to_server(inner_service);
-    add_services(Server::builder())
+    recorded_router(inner_service, &recorder, options)
+        .await
         .serve(bind_addr)
         .await
         .context("error initializing server")
 }
 
+/// The router [`serve_recorded`] serves, for tests to serve on a port of their own. Calls the
+/// middleware rejects are recorded too.
+pub async fn recorded_router<T: VectorService>(
+    inner_service: T,
+    recorder: &Recorder,
+    options: ServeOptions,
+) -> Router<ServeLayers<RecorderLayer>> {
+    serving::router_with_layer(inner_service, overrides(), options, recorder.layer()).await
+}
+
+/// Serves like [`serve`], with `faults` injected into every versioned call and the
+/// `API.admin.FaultAdmin` service to change them while running.
+pub async fn serve_simulator<T: VectorService>(
+    port: u16,
+    inner_service: T,
+    faults: Faults,
+    options: ServeOptions,
+) -> anyhow::Result<()> {
+    let bind_addr = format!("0.0.0.0:{}", port).parse()?;
+
+    simulator_router(inner_service, &faults, options)
+        .await
+        .serve(bind_addr)
+        .await
+        .context("error initializing simulator")
+}
+
+/// The router [`serve_simulator`] serves, for tests to serve on a port of their own.
+pub async fn simulator_router<T: VectorService>(
+    inner_service: T,
+    faults: &Faults,
+    options: ServeOptions,
+) -> Router<ServeLayers<FaultLayer>> {
+    serving::router_with_layer(inner_service, overrides(), options, faults.layer())
+        .await
+        .add_service(faults.admin_service())
+}
+
+// legacy semantics the inner service no longer implements
+pub fn overrides() -> VersionOverrides {
+    let mut overrides = VersionOverrides::default();
+    overrides.v1.sum(EmptySumAsZero);
+    overrides
+}
+
+// V1 `Sum` on a request without a vector used to return 0.0 rather than an error
+struct EmptySumAsZero;
+#[async_trait]
+impl MethodOverride<v1::SumRequest, v1::SumResponse> for EmptySumAsZero {
+    async fn call(
+        &self,
+        request: Request<v1::SumRequest>,
+        next: Next<'_, v1::SumRequest, v1::SumResponse>,
+    ) -> Result<Response<v1::SumResponse>, tonic::Status> {
+        if request.get_ref().vector.is_none() {
+            return Ok(Response::new(v1::SumResponse { sum: 0.0 }));
+        }
+        next.run(request).await
+    }
+}
+
 #[derive(Clone)]
 pub struct VectorHandler {
     pub name: String,
@@ -28,9 +148,11 @@ impl VectorService for VectorHandler {
         request: Request<PrintRequest>,
     ) -> Result<Response<PrintResponse>, tonic::Status> {
         let name = &self.name;
+        let version = version_name(&request);
+        let caller = caller_name(&request);
         let vector = request.into_inner().vector;
 
-        println!("{name} VectorService print: {vector:?}");
+        println!("{name} VectorService print ({version}, {caller}): {vector:?}");
 
         Ok(Response::new(PrintResponse {
             printed_count: vector.iter().len() as u32,
@@ -41,14 +163,47 @@ impl VectorService for VectorHandler {
         request: Request<SumRequest>,
     ) -> Result<Response<SumResponse>, tonic::Status> {
         let name = &self.name;
+        let version = version_name(&request);
+        let caller = caller_name(&request);
+        let deadline = deadline(&request);
+        let cancelled = cancellation(&request).unwrap_or_default();
         let vectors = request.into_inner().vectors;
-        let sum = vectors
-            .iter()
-            .map(|vector| vector.values.iter().sum())
-            .collect();
+        // on a blocking thread, which stops early once the call is dropped or out of time
+        let sum = tokio::task::spawn_blocking(move || sum_vectors(&vectors, deadline, &cancelled))
+            .await
+            .map_err(|error| tonic::Status::internal(error.to_string()))??;
 
-        println!("{name} VectorService sum: {sum:?}");
+        println!("{name} VectorService sum ({version}, {caller}): {sum:?}");
 
         Ok(Response::new(SumResponse { sum }))
     }
 }
+
+fn sum_vectors(
+    vectors: &[Vector],
+    deadline: Option<Deadline>,
+    cancelled: &CancellationToken,
+) -> Result<Vec<f32>, tonic::Status> {
+    vectors
+        .iter()
+        .map(|vector| {
+            if cancelled.is_cancelled() {
+                return Err(tonic::Status::cancelled("the call was cancelled"));
+            }
+            if deadline.is_some_and(Deadline::is_expired) {
+                return Err(tonic::Status::deadline_exceeded(
+                    "the deadline passed while summing",
+                ));
+            }
+            Ok(vector.values.iter().sum())
+        })
+        .collect()
+}
+
+fn version_name<T>(request: &Request<T>) -> &'static str {
+    api_version(request).map_or("unversioned", |version| version.name)
+}
+
+fn caller_name<T>(request: &Request<T>) -> String {
+    principal(request).map_or("anonymous".to_string(), |principal| principal.name.clone())
+}
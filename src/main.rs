@@ -1,4 +1,6 @@
-use versioning_grpc::{serve, VectorHandler};
+use protos::auth::Authenticator;
+use protos::recording::Recorder;
+use versioning_grpc::{serve, serve_recorded, ServeOptions, VectorHandler};
 
 #[tokio::main]
 async fn main() {
@@ -6,38 +8,60 @@ async fn main() {
     let inner_service = VectorHandler {
         name: "my name".to_string(),
     };
-    serve(port, inner_service).await.unwrap()
+    let options = ServeOptions {
+        authenticator: authenticator(),
+        ..Default::default()
+    };
+    // RECORD_TO=<file> records every call, for `replay` to re-issue against another build
+    match std::env::var_os("RECORD_TO") {
+        Some(path) => {
+            let recorder = Recorder::create(path).unwrap();
+            serve_recorded(port, inner_service, recorder, options)
+                .await
+                .unwrap()
+        }
+        None => serve(port, inner_service, options).await.unwrap(),
+    }
+}
+
+// AUTH_KEYS=<keys.toml> and AUTH_JWKS=<jwks.json> turn on authentication, see `auth/keys.toml`
+fn authenticator() -> Option<Authenticator> {
+    let keys = std::env::var_os("AUTH_KEYS");
+    let jwks = std::env::var_os("AUTH_JWKS");
+    if keys.is_none() && jwks.is_none() {
+        return None;
+    }
+    let mut authenticator = Authenticator::new();
+    if let Some(path) = keys {
+        authenticator = authenticator.with_keys_file(path).unwrap();
+    }
+    if let Some(path) = jwks {
+        authenticator = authenticator.with_jwks_file(path).unwrap();
+    }
+    Some(authenticator)
 }
 
 #[cfg(test)]
 mod tests {
+    use protos::testing::TestServer;
     use protos::vector_service::vector_service_client::{SupportedVersion, VectorServiceClient};
-    use protos::vector_service::{PrintRequest, SumRequest, Vector};
+    use protos::vector_service::{PrintRequest, SumRequest, Vector, API_VERSION_HEADER};
     use std::time::Duration;
     use tokio::time::sleep;
     use tonic::transport::Uri;
-    use versioning_grpc::{serve, VectorHandler};
+    use versioning_grpc::proxy::{serve_proxy_on, ProxyConfig};
+    use versioning_grpc::{overrides, ServeOptions, VectorHandler};
 
     #[tokio::test]
     async fn simple_test() {
-        let port = 1818;
-        let address: Uri = format!("https://0.0.0.0:{}", port).parse().unwrap();
-        println!("Connecting to {}", address);
         let inner_service = VectorHandler {
             name: "my name".to_string(),
         };
-        let server_handle = tokio::spawn(async move {
-            let _ = serve(port, inner_service.clone()).await;
-        });
-        sleep(Duration::from_secs(1)).await;
+        let server = TestServer::start(inner_service, overrides()).await.unwrap();
+        println!("Connecting to {:?}", server.local_addr());
 
-        let mut client_v1 =
-            VectorServiceClient::connect_versioned(address.clone(), SupportedVersion::V1)
-                .await
-                .unwrap();
-        let mut client_v2 = VectorServiceClient::connect_versioned(address, SupportedVersion::V2)
-            .await
-            .unwrap();
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let mut client_v2 = server.client(SupportedVersion::V2);
         let vec1 = Vector {
             id: "id1".parse().unwrap(),
             values: vec![1., 1., 1.],
@@ -70,9 +94,1882 @@ mod tests {
         let sum_result2 = client_v2.sum(sum_request_2).await;
         println!("sum result 2: {sum_result2:?}");
 
-        server_handle.abort();
-        // Wait for the server to shut down
-        let _ = server_handle.await;
+        drop((client_v1, client_v2));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn version_header_test() {
+        let inner_service = VectorHandler {
+            name: "versioned".to_string(),
+        };
+        let server = TestServer::start(inner_service, overrides()).await.unwrap();
+
+        for (version, expected) in [(SupportedVersion::V1, "V1"), (SupportedVersion::V2, "V2")] {
+            let mut client = server.client(version);
+            let request = SumRequest {
+                vectors: vec![Vector {
+                    id: "id1".to_string(),
+                    values: vec![1., 2.],
+                }],
+            };
+            let response = client.sum(request).await.unwrap();
+            let served = response.metadata().get(API_VERSION_HEADER).unwrap();
+            assert_eq!(served, expected);
+        }
+    }
+
+    #[tokio::test]
+    // V1 can only sum one vector per call, so the client splits the request and merges the sums
+    async fn fan_out_test() {
+        let inner_service = VectorHandler {
+            name: "fan_out".to_string(),
+        };
+        let server = TestServer::start_in_memory(inner_service, overrides())
+            .await
+            .unwrap();
+
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let vectors = (1..=5)
+            .map(|i| Vector {
+                id: format!("id{i}"),
+                values: vec![i as f32; 3],
+            })
+            .collect();
+
+        let response = client_v1.sum(SumRequest { vectors }).await.unwrap();
+        assert_eq!(response.into_inner().sum, vec![3., 6., 9., 12., 15.]);
+
+        let response = client_v1.sum(SumRequest { vectors: vec![] }).await.unwrap();
+        assert!(response.into_inner().sum.is_empty());
+
+        drop(client_v1);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // the typed client speaks each version's own messages, picked at compile time
+    async fn typed_client_test() {
+        use protos::api::{v1, v2};
+        use protos::vector_service::typed_vector_service_client::{
+            api_versions, VectorServiceClient as TypedClient,
+        };
+
+        let inner_service = VectorHandler {
+            name: "typed".to_string(),
+        };
+        let server = TestServer::start_in_memory(inner_service, overrides())
+            .await
+            .unwrap();
+
+        let mut client_v1 = TypedClient::<api_versions::V1>::new(server.channel());
+        let mut client_v2 = TypedClient::<api_versions::V2>::new(server.channel());
+        let vector = v1::Vector {
+            id: "id1".to_string(),
+            values: vec![1., 2.],
+        };
+
+        let response = client_v1
+            .sum(v1::SumRequest {
+                vector: Some(vector.clone()),
+            })
+            .await
+            .unwrap();
+        let sum: f32 = response.into_inner().sum;
+        assert_eq!(sum, 3.0);
+
+        let response = client_v2
+            .sum(v2::SumRequest {
+                vectors: vec![vector.clone(), vector.clone()],
+            })
+            .await
+            .unwrap();
+        let sums: Vec<f32> = response.into_inner().sum;
+        assert_eq!(sums, vec![3.0, 3.0]);
+
+        let response = client_v1
+            .print(v1::PrintRequest {
+                vector: Some(vector),
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().printed_count, 1);
+    }
+
+    #[test]
+    // methods a version doesn't list don't compile, instead of failing as unimplemented at runtime
+    fn typed_client_compile_test() {
+        trybuild::TestCases::new().compile_fail("fixtures/compile_fail/*.rs");
+    }
+
+    #[tokio::test]
+    async fn capabilities_test() {
+        use protos::vector_service::vector_service_client::{Feature, Method};
+        use tonic::transport::Endpoint;
+
+        assert_eq!(
+            SupportedVersion::all(),
+            &[SupportedVersion::V1, SupportedVersion::V2]
+        );
+        let channel = Endpoint::from_static("http://0.0.0.0:1").connect_lazy();
+        for &version in SupportedVersion::all() {
+            let client = VectorServiceClient::new_versioned(channel.clone(), version);
+            assert_eq!(client.version(), version);
+            assert!(client.capabilities().has_method(Method::Sum));
+        }
+
+        let client_v1 = VectorServiceClient::new_versioned(channel.clone(), SupportedVersion::V1);
+        let client_v2 = VectorServiceClient::new_versioned(channel, SupportedVersion::V2);
+        assert!(!client_v1.supports(Method::Sum, Feature::MultiVector));
+        assert!(client_v2.supports(Method::Sum, Feature::MultiVector));
+        assert!(!client_v2.supports(Method::Print, Feature::MultiVector));
+        assert!(SupportedVersion::V1.api_version().deprecated);
+
+        // the declared methods are exactly those the generated server routes
+        let mut pool = prost_reflect::DescriptorPool::new();
+        for descriptor_set in protos::descriptors::ALL {
+            pool.decode_file_descriptor_set(descriptor_set).unwrap();
+        }
+        for &version in SupportedVersion::all() {
+            let service = pool.get_service_by_name(version.service_name()).unwrap();
+            let mut served: Vec<_> = service
+                .methods()
+                .map(|method| format!("/{}/{}", service.full_name(), method.name()))
+                .collect();
+            let mut declared: Vec<_> = version
+                .capabilities()
+                .methods
+                .iter()
+                .map(|method| format!("/{}/{}", version.service_name(), method.name()))
+                .collect();
+            served.sort();
+            declared.sort();
+            assert_eq!(
+                declared, served,
+                "{version:?} declares other methods than it serves"
+            );
+        }
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    // interceptors and layers must not silently fall back to V1
+    async fn intercepted_client_test() {
+        use std::sync::{Arc, Mutex};
+        use tonic::codegen::GrpcMethod;
+
+        let inner_service = VectorHandler {
+            name: "intercepted".to_string(),
+        };
+        let server = TestServer::start(inner_service, overrides()).await.unwrap();
+
+        let services = Arc::new(Mutex::new(Vec::new()));
+        let recorder = {
+            let services = services.clone();
+            move |request: tonic::Request<()>| {
+                let method = request.extensions().get::<GrpcMethod>().unwrap();
+                services.lock().unwrap().push(method.service().to_string());
+                Ok(request)
+            }
+        };
+        let channel = server.channel();
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id1".to_string(),
+                values: vec![1., 2.],
+            }],
+        };
+
+        let mut intercepted = VectorServiceClient::with_interceptor_versioned(
+            channel.clone(),
+            recorder.clone(),
+            SupportedVersion::V2,
+        );
+        let response = intercepted.sum(request()).await.unwrap();
+        assert_eq!(response.metadata().get(API_VERSION_HEADER).unwrap(), "V2");
+
+        let mut layered = VectorServiceClient::with_layer_versioned(
+            channel,
+            tonic::service::interceptor(recorder),
+            SupportedVersion::V2,
+        );
+        layered.sum(request()).await.unwrap();
+
+        assert_eq!(
+            *services.lock().unwrap(),
+            vec!["API.V2.VectorService", "API.V2.VectorService"]
+        );
+
+        drop((intercepted, layered));
+        server.shutdown().await.unwrap();
+    }
+
+    // faults the policy test injects into V2 `Sum`, one per call, in order
+    enum Fault {
+        Fail,
+        Reject,
+        Slow(Duration),
+    }
+
+    struct FaultInjector(std::sync::Arc<std::sync::Mutex<std::collections::VecDeque<Fault>>>);
+    #[tonic::async_trait]
+    impl
+        protos::vector_service::vector_service_server::MethodOverride<
+            protos::api::v2::SumRequest,
+            protos::api::v2::SumResponse,
+        > for FaultInjector
+    {
+        async fn call(
+            &self,
+            request: tonic::Request<protos::api::v2::SumRequest>,
+            next: protos::vector_service::vector_service_server::Next<
+                '_,
+                protos::api::v2::SumRequest,
+                protos::api::v2::SumResponse,
+            >,
+        ) -> Result<tonic::Response<protos::api::v2::SumResponse>, tonic::Status> {
+            let fault = self.0.lock().unwrap().pop_front();
+            match fault {
+                Some(Fault::Fail) => return Err(tonic::Status::unavailable("injected failure")),
+                Some(Fault::Reject) => {
+                    return Err(tonic::Status::invalid_argument("injected rejection"))
+                }
+                Some(Fault::Slow(delay)) => sleep(delay).await,
+                None => {}
+            }
+            next.run(request).await
+        }
+    }
+
+    #[tokio::test]
+    // retries recover from transient failures, timeouts bound slow calls and hedging works around them
+    async fn policy_test() {
+        use protos::vector_service::vector_service_client::{
+            CallPolicy, HedgingPolicy, Method, PolicyClient, RetryPolicy,
+        };
+        use protos::vector_service::vector_service_server::VersionOverrides;
+        use std::collections::VecDeque;
+        use std::sync::{Arc, Mutex};
+        use tokio::time::Instant;
+
+        let faults = Arc::new(Mutex::new(VecDeque::new()));
+        let mut overrides = VersionOverrides::default();
+        overrides.v2.sum(FaultInjector(faults.clone()));
+        let inner_service = VectorHandler {
+            name: "faulty".to_string(),
+        };
+        let server = TestServer::start(inner_service, overrides).await.unwrap();
+        let client = server.client(SupportedVersion::V2);
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id1".to_string(),
+                values: vec![1., 2.],
+            }],
+        };
+
+        faults.lock().unwrap().extend([Fault::Fail, Fault::Fail]);
+        let mut retried = PolicyClient::new(
+            client.clone(),
+            CallPolicy::default().retry(RetryPolicy {
+                initial_backoff: Duration::from_millis(10),
+                ..Default::default()
+            }),
+        );
+        let response = retried.sum(request()).await.unwrap();
+        assert_eq!(response.into_inner().sum, vec![3.]);
+        assert!(faults.lock().unwrap().is_empty());
+
+        faults
+            .lock()
+            .unwrap()
+            .push_back(Fault::Slow(Duration::from_secs(2)));
+        let mut bounded = PolicyClient::new(
+            client.clone(),
+            CallPolicy::default().timeout(Method::Sum, Duration::from_millis(200)),
+        );
+        let status = bounded.sum(request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::DeadlineExceeded);
+
+        faults
+            .lock()
+            .unwrap()
+            .push_back(Fault::Slow(Duration::from_secs(2)));
+        let mut hedged = PolicyClient::new(
+            client.clone(),
+            CallPolicy::default().hedging(HedgingPolicy {
+                delay: Duration::from_millis(50),
+                ..Default::default()
+            }),
+        );
+        let started = Instant::now();
+        let response = hedged.sum(request()).await.unwrap();
+        assert_eq!(response.into_inner().sum, vec![3.]);
+        assert!(started.elapsed() < Duration::from_secs(1));
+
+        // a rejection is the answer, the failure queued behind it is never reached
+        faults.lock().unwrap().extend([Fault::Reject, Fault::Fail]);
+        let status = hedged.sum(request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        assert_eq!(faults.lock().unwrap().len(), 1);
+
+        drop((client, retried, bounded, hedged));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // calls are only balanced across the endpoints that are up and serve the client's version
+    async fn balanced_test() {
+        use protos::vector_service::vector_service_client::{
+            BalanceConfig, BalanceStrategy, BalancedChannel, StaticResolver,
+        };
+        use protos::vector_service::vector_service_server::{VersionAdapter, VersionOverrides};
+
+        let inner_service = VectorHandler {
+            name: "balanced".to_string(),
+        };
+        let server = TestServer::start(inner_service, overrides()).await.unwrap();
+        let (mut reporter, health) = tonic_health::server::health_reporter();
+        reporter
+            .set_service_status(
+                protos::api::v1::SERVICE_NAME,
+                tonic_health::ServingStatus::Serving,
+            )
+            .await;
+        let adapter = VersionAdapter::new(
+            VectorHandler {
+                name: "v1 only".to_string(),
+            },
+            VersionOverrides::default(),
+        );
+        let v1_only = TestServer::start_router(
+            tonic::transport::Server::builder()
+                .add_service(health)
+                .add_service(
+                    protos::api::v1::vector_service_server::VectorServiceServer::new(adapter),
+                ),
+        )
+        .await
+        .unwrap();
+        // nothing listens on a port the OS handed out and got back
+        let down_port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        let port = server.local_addr().unwrap().port();
+        let v1_only_port = v1_only.local_addr().unwrap().port();
+
+        let uri = |port: u16| -> Uri { format!("http://127.0.0.1:{port}").parse().unwrap() };
+        let resolver = StaticResolver::new([uri(port), uri(v1_only_port), uri(down_port)]);
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id1".to_string(),
+                values: vec![1., 2.],
+            }],
+        };
+
+        let channel_v1 = BalancedChannel::new(
+            resolver.clone(),
+            SupportedVersion::V1.service_name(),
+            BalanceConfig::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            channel_v1.healthy_endpoints(),
+            vec![uri(port), uri(v1_only_port)]
+        );
+        let mut client_v1 = VectorServiceClient::new_versioned(channel_v1, SupportedVersion::V1);
+        for _ in 0..4 {
+            let response = client_v1.sum(request()).await.unwrap();
+            assert_eq!(response.metadata().get(API_VERSION_HEADER).unwrap(), "V1");
+        }
+
+        let config = BalanceConfig {
+            strategy: BalanceStrategy::PowerOfTwoChoices,
+            ..Default::default()
+        };
+        let channel_v2 =
+            BalancedChannel::new(resolver, SupportedVersion::V2.service_name(), config)
+                .await
+                .unwrap();
+        assert_eq!(channel_v2.healthy_endpoints(), vec![uri(port)]);
+        let mut client_v2 = VectorServiceClient::new_versioned(channel_v2, SupportedVersion::V2);
+        for _ in 0..4 {
+            let response = client_v2.sum(request()).await.unwrap();
+            assert_eq!(response.into_inner().sum, vec![3.]);
+        }
+
+        let nothing_serves_v2 = StaticResolver::new([uri(v1_only_port)]);
+        let mut client = VectorServiceClient::connect_balanced(
+            nothing_serves_v2,
+            BalanceConfig::default(),
+            SupportedVersion::V2,
+        )
+        .await
+        .unwrap();
+        let status = client.sum(request()).await.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::Unavailable);
+
+        drop((client_v1, client_v2, client));
+        v1_only.shutdown().await.unwrap();
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    // concurrent V2 sums share a single request, V1 sums are sent one by one
+    async fn batching_test() {
+        use futures_util::future::join_all;
+        use protos::validation::ValidationConfig;
+        use protos::vector_service::vector_service_client::{BatchConfig, BatchingClient};
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use tonic_types::StatusExt;
+
+        let inner_service = VectorHandler {
+            name: "batched".to_string(),
+        };
+        let options = ServeOptions {
+            validation: ValidationConfig {
+                max_vectors: Some(4),
+                dimension: Some(2),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = TestServer::start_with(inner_service, overrides(), options)
+            .await
+            .unwrap();
+        let channel = server.channel();
+        let sent = Arc::new(AtomicUsize::new(0));
+        let counter = {
+            let sent = sent.clone();
+            move |request: tonic::Request<()>| {
+                sent.fetch_add(1, Ordering::Relaxed);
+                Ok(request)
+            }
+        };
+        let request = |first: f32, count: usize| SumRequest {
+            vectors: (0..count)
+                .map(|i| Vector {
+                    id: format!("id{i}"),
+                    values: vec![first + i as f32, 1.],
+                })
+                .collect(),
+        };
+        let config = BatchConfig {
+            window: Duration::from_millis(50),
+            max_vectors: 4,
+        };
+
+        let client_v2 = VectorServiceClient::with_interceptor_versioned(
+            channel.clone(),
+            counter.clone(),
+            SupportedVersion::V2,
+        );
+        let batching = BatchingClient::new(client_v2, config.clone());
+        let responses = join_all((0..5).map(|i| batching.sum(request(i as f32 * 10., 2)))).await;
+        for (i, response) in responses.into_iter().enumerate() {
+            let first = i as f32 * 10.;
+            assert_eq!(
+                response.unwrap().into_inner().sum,
+                vec![first + 1., first + 2.]
+            );
+        }
+        // two calls at most fit in a batch the server accepts
+        assert_eq!(sent.load(Ordering::Relaxed), 3);
+
+        // an invalid call fails alone, with the paths of its own request
+        sent.store(0, Ordering::Relaxed);
+        let mut invalid = request(100., 1);
+        invalid.vectors[0].values.push(1.);
+        let responses = join_all([
+            batching.sum(request(0., 1)),
+            batching.sum(invalid),
+            batching.sum(request(10., 1)),
+        ])
+        .await;
+        let [first, invalid, last] = <[_; 3]>::try_from(responses).unwrap();
+        assert_eq!(first.unwrap().into_inner().sum, vec![1.]);
+        assert_eq!(last.unwrap().into_inner().sum, vec![11.]);
+        let status = invalid.unwrap_err();
+        assert_eq!(status.code(), tonic::Code::InvalidArgument);
+        let violations = status.get_details_bad_request().unwrap().field_violations;
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].field, "vectors[0].values");
+        // the rejected batch, then one call per caller
+        assert_eq!(sent.load(Ordering::Relaxed), 4);
+
+        sent.store(0, Ordering::Relaxed);
+        let client_v1 =
+            VectorServiceClient::with_interceptor_versioned(channel, counter, SupportedVersion::V1);
+        let batching = BatchingClient::new(client_v1, config);
+        let responses = join_all((0..3).map(|i| batching.sum(request(i as f32, 1)))).await;
+        for (i, response) in responses.into_iter().enumerate() {
+            assert_eq!(response.unwrap().into_inner().sum, vec![i as f32 + 1.]);
+        }
+        assert_eq!(sent.load(Ordering::Relaxed), 3);
+
+        drop(batching);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // every client against every served version: old clients keep working on newer servers,
+    // newer clients are turned away by older ones
+    async fn compatibility_matrix_test() {
+        use protos::testing::compatibility_matrix::{self, Call, ClientKind, Outcome};
+
+        let vector = |i: usize| Vector {
+            id: format!("id{i}"),
+            values: vec![i as f32, 1.],
+        };
+        let single_vector = [
+            Call::Print(PrintRequest {
+                vector: Some(vector(1)),
+            }),
+            Call::Sum(SumRequest {
+                vectors: vec![vector(2)],
+            }),
+        ];
+        let multi_vector = [Call::Sum(SumRequest {
+            vectors: (1..=3).map(vector).collect(),
+        })];
+
+        for calls in [&single_vector[..], &multi_vector[..]] {
+            let inner_service = VectorHandler {
+                name: "matrix".to_string(),
+            };
+            let matrix = compatibility_matrix::run(inner_service, overrides(), calls)
+                .await
+                .unwrap();
+            println!("{matrix}");
+            assert_eq!(matrix.failures().count(), 0);
+            for client in ClientKind::all() {
+                let (ClientKind::Raw(version) | ClientKind::Versioned(version)) = client;
+                for &served in SupportedVersion::all() {
+                    let outcome = matrix.get(client, served).unwrap();
+                    let too_new = version.api_version().ordinal > served.api_version().ordinal;
+                    let raw_v1_batch = client == ClientKind::Raw(SupportedVersion::V1)
+                        && calls.len() == multi_vector.len();
+                    match too_new || raw_v1_batch {
+                        true => assert!(matches!(outcome, Outcome::Unsupported(_)), "{outcome:?}"),
+                        false => assert_eq!(*outcome, Outcome::Pass),
+                    }
+                }
+            }
+        }
+    }
+
+    mod round_trips {
+        use proptest::prelude::*;
+        use protos::api::v1;
+        use protos::api_versions::{V1, V2};
+        use protos::conversions::{kinds, Downgrade, MessageKind, RoundTrip, Upgrade};
+        use protos::testing::strategies;
+        use protos::vector_service::vector_service_server::{VersionAdapter, VersionOverrides};
+        use std::fmt::Debug;
+        use tonic::Request;
+        use versioning_grpc::VectorHandler;
+
+        // upgrading and downgrading back gives the original message, or an error where the step is lossy
+        fn check<K, V>(message: K::Of<V>)
+        where
+            K: MessageKind,
+            V: Upgrade<K> + Downgrade<K>,
+            K::Of<V>: Clone + PartialEq + Debug,
+        {
+            let round_trip = V::downgrade(V::upgrade(message.clone()));
+            match <V as Downgrade<K>>::ROUND_TRIP {
+                RoundTrip::Lossless => assert_eq!(round_trip, Ok(message)),
+                RoundTrip::Lossy(_) => {
+                    if let Ok(round_trip) = round_trip {
+                        assert_eq!(round_trip, message);
+                    }
+                }
+            }
+        }
+
+        proptest! {
+            #[test]
+            fn v1_print_request(message in strategies::v1::print_request()) {
+                check::<kinds::PrintRequest, V1>(message);
+            }
+
+            #[test]
+            fn v1_print_response(message in strategies::v1::print_response()) {
+                check::<kinds::PrintResponse, V1>(message);
+            }
+
+            #[test]
+            fn v1_sum_request(message in strategies::v1::sum_request()) {
+                check::<kinds::SumRequest, V1>(message);
+            }
+
+            #[test]
+            fn v1_sum_response(message in strategies::v1::sum_response()) {
+                check::<kinds::SumResponse, V1>(message);
+            }
+
+            #[test]
+            fn v2_print_request(message in strategies::v2::print_request()) {
+                check::<kinds::PrintRequest, V2>(message);
+            }
+
+            #[test]
+            fn v2_print_response(message in strategies::v2::print_response()) {
+                check::<kinds::PrintResponse, V2>(message);
+            }
+
+            #[test]
+            fn v2_sum_request(message in strategies::v2::sum_request()) {
+                check::<kinds::SumRequest, V2>(message);
+            }
+
+            #[test]
+            fn v2_sum_response(message in strategies::v2::sum_response()) {
+                check::<kinds::SumResponse, V2>(message);
+            }
+
+            #[test]
+            // without the legacy override, a V1 sum of no vector is an error rather than a panic
+            fn v1_sum_adapter(message in strategies::v1::sum_request()) {
+                let adapter = VersionAdapter::new(
+                    VectorHandler {
+                        name: "round trip".to_string(),
+                    },
+                    VersionOverrides::default(),
+                );
+                let runtime = tokio::runtime::Builder::new_current_thread().build().unwrap();
+                let expected = message.vector.as_ref().map(|vector| vector.values.iter().sum::<f32>());
+                let response = runtime.block_on(v1::vector_service_server::VectorService::sum(
+                    &adapter,
+                    Request::new(message),
+                ));
+                match expected {
+                    Some(expected) => {
+                        let sum = response.unwrap().into_inner().sum;
+                        prop_assert!(sum == expected || (sum.is_nan() && expected.is_nan()));
+                    }
+                    None => prop_assert_eq!(response.unwrap_err().code(), tonic::Code::Internal),
+                }
+            }
+        }
+    }
+
+    // checks one call against the committed wire bytes in `fixtures/golden`: the request must decode to
+    // `request` and encode back to the same bytes, and the adapter must answer the golden response bytes.
+    // `UPDATE_GOLDEN=1 cargo test golden` rewrites the fixtures after a deliberate wire change.
+    async fn check_golden<Req, Resp, F, Fut>(name: &str, request: Req, call: F)
+    where
+        Req: prost::Message + Default + PartialEq + std::fmt::Debug,
+        Resp: prost::Message,
+        F: FnOnce(tonic::Request<Req>) -> Fut,
+        Fut: std::future::Future<Output = Result<tonic::Response<Resp>, tonic::Status>>,
+    {
+        let golden = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/golden");
+        let request_path = golden.join(format!("{name}.request.bin"));
+        let response_path = golden.join(format!("{name}.response.bin"));
+        let update = std::env::var_os("UPDATE_GOLDEN").is_some();
+        let read = |path: &std::path::Path| {
+            std::fs::read(path).unwrap_or_else(|e| panic!("error reading golden {path:?}: {e}"))
+        };
+
+        if update {
+            std::fs::create_dir_all(request_path.parent().unwrap()).unwrap();
+            std::fs::write(&request_path, request.encode_to_vec()).unwrap();
+        }
+        let request_bytes = read(&request_path);
+        let decoded = Req::decode(request_bytes.as_slice()).unwrap();
+        assert_eq!(
+            decoded, request,
+            "{name}: the golden request decodes differently"
+        );
+        assert_eq!(
+            decoded.encode_to_vec(),
+            request_bytes,
+            "{name}: the golden request encodes differently"
+        );
+
+        let response = call(tonic::Request::new(decoded)).await.unwrap();
+        let response_bytes = response.get_ref().encode_to_vec();
+        if update {
+            std::fs::write(&response_path, &response_bytes).unwrap();
+        }
+        assert_eq!(
+            response_bytes,
+            read(&response_path),
+            "{name}: the response differs from the golden one"
+        );
+    }
+
+    #[tokio::test]
+    // requests as released clients of every version encode them, answered as released servers did
+    async fn golden_test() {
+        use protos::api::{v1, v2};
+        use protos::vector_service::vector_service_server::VersionAdapter;
+
+        let adapter = VersionAdapter::new(
+            VectorHandler {
+                name: "golden".to_string(),
+            },
+            overrides(),
+        );
+        let vector = |id: &str, values: &[f32]| v1::Vector {
+            id: id.to_string(),
+            values: values.to_vec(),
+        };
+
+        let request = v1::PrintRequest {
+            vector: Some(vector("id1", &[1., 2., 3.])),
+        };
+        check_golden("v1/print", request, |request| {
+            v1::vector_service_server::VectorService::print(&adapter, request)
+        })
+        .await;
+        let request = v1::SumRequest {
+            vector: Some(vector("id1", &[1., 2., 3.])),
+        };
+        check_golden("v1/sum", request, |request| {
+            v1::vector_service_server::VectorService::sum(&adapter, request)
+        })
+        .await;
+        let request = v1::SumRequest { vector: None };
+        check_golden("v1/sum_empty", request, |request| {
+            v1::vector_service_server::VectorService::sum(&adapter, request)
+        })
+        .await;
+
+        let request = v2::PrintRequest {
+            vector: Some(vector("id1", &[1., 2., 3.])),
+        };
+        check_golden("v2/print", request, |request| {
+            v2::vector_service_server::VectorService::print(&adapter, request)
+        })
+        .await;
+        let request = v2::SumRequest {
+            vectors: vec![vector("id1", &[1., 2., 3.]), vector("id2", &[-1.5, 0.25])],
+        };
+        check_golden("v2/sum", request, |request| {
+            v2::vector_service_server::VectorService::sum(&adapter, request)
+        })
+        .await;
+        let request = v2::SumRequest { vectors: vec![] };
+        check_golden("v2/sum_empty", request, |request| {
+            v2::vector_service_server::VectorService::sum(&adapter, request)
+        })
+        .await;
+
+        // decoded by hand, so regenerating the goldens can't hide a change of the encoding
+        let golden = |name: &str| {
+            let golden = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures/golden");
+            std::fs::read(golden.join(name)).unwrap()
+        };
+        #[rustfmt::skip]
+        let vector_id1 = [
+            // field 1, `vector` or `vectors`, length-delimited: 20 bytes
+            0x0a, 0x14,
+            // field 1 `id`, length-delimited: 3 bytes of "id1"
+            0x0a, 0x03, b'i', b'd', b'1',
+            // field 2 `values`, one fixed32 each: 1.0, 2.0 and 3.0
+            0x15, 0x00, 0x00, 0x80, 0x3f,
+            0x15, 0x00, 0x00, 0x00, 0x40,
+            0x15, 0x00, 0x00, 0x40, 0x40,
+        ];
+        #[rustfmt::skip]
+        let vector_id2 = [
+            0x0a, 0x0f,
+            0x0a, 0x03, b'i', b'd', b'2',
+            // -1.5 and 0.25
+            0x15, 0x00, 0x00, 0xc0, 0xbf,
+            0x15, 0x00, 0x00, 0x80, 0x3e,
+        ];
+        assert_eq!(golden("v1/print.request.bin"), vector_id1);
+        assert_eq!(golden("v1/sum.request.bin"), vector_id1);
+        assert_eq!(golden("v2/print.request.bin"), vector_id1);
+        assert_eq!(
+            golden("v2/sum.request.bin"),
+            [&vector_id1[..], &vector_id2[..]].concat()
+        );
+        // field 1 `printed_count`, varint 1
+        assert_eq!(golden("v1/print.response.bin"), [0x08, 0x01]);
+        assert_eq!(golden("v2/print.response.bin"), [0x08, 0x01]);
+        // field 1 `sum`, a single fixed32 6.0
+        assert_eq!(
+            golden("v1/sum.response.bin"),
+            [0x0d, 0x00, 0x00, 0xc0, 0x40]
+        );
+        // field 1 `sum`, packed in 8 bytes: 6.0 and -1.25
+        assert_eq!(
+            golden("v2/sum.response.bin"),
+            [0x0a, 0x08, 0x00, 0x00, 0xc0, 0x40, 0x00, 0x00, 0xa0, 0xbf]
+        );
+        // an empty request has no fields, and neither has V1's sum of 0 or V2's empty list of sums
+        for name in ["v1/sum_empty", "v2/sum_empty"] {
+            assert!(golden(&format!("{name}.request.bin")).is_empty());
+            assert!(golden(&format!("{name}.response.bin")).is_empty());
+        }
+    }
+
+    #[tokio::test]
+    // client code runs against scripted answers, served as every version
+    async fn mock_test() {
+        use protos::testing::MockVectorService;
+        use protos::vector_service::vector_service_server::VersionOverrides;
+        use protos::vector_service::SumResponse;
+        use tonic::{Code, Status};
+
+        let mock = MockVectorService::new();
+        let server = TestServer::start_in_memory(mock.clone(), VersionOverrides::default())
+            .await
+            .unwrap();
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let mut client_v2 = server.client(SupportedVersion::V2);
+        let request = |count: usize| SumRequest {
+            vectors: (0..count)
+                .map(|i| Vector {
+                    id: format!("id{i}"),
+                    values: vec![i as f32],
+                })
+                .collect(),
+        };
+
+        mock.push_sum(Ok(SumResponse { sum: vec![4.] }))
+            .push_sum(Ok(SumResponse { sum: vec![5., 6.] }))
+            .push_sum(Err(Status::unavailable("injected")))
+            // more sums than V1 can answer with
+            .push_sum(Ok(SumResponse { sum: vec![7., 8.] }));
+        let response = client_v1.sum(request(1)).await.unwrap();
+        assert_eq!(response.into_inner().sum, vec![4.]);
+        let response = client_v2.sum(request(2)).await.unwrap();
+        assert_eq!(response.into_inner().sum, vec![5., 6.]);
+        let status = client_v2.sum(request(2)).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+        let status = client_v1.sum(request(1)).await.unwrap_err();
+        assert_eq!(status.code(), Code::Internal);
+        let status = client_v2.sum(request(1)).await.unwrap_err();
+        assert_eq!(status.code(), Code::FailedPrecondition);
+
+        let recorded = mock.sum_requests();
+        let versions: Vec<_> = recorded
+            .iter()
+            .map(|recorded| recorded.version.unwrap().name)
+            .collect();
+        assert_eq!(versions, vec!["V1", "V2", "V2", "V1", "V2"]);
+        assert_eq!(recorded[1].message, request(2));
+
+        mock.set_latency(Duration::from_millis(200));
+        mock.push_sum(Ok(SumResponse { sum: vec![1.] }));
+        let started = tokio::time::Instant::now();
+        client_v2.sum(request(1)).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+    }
+
+    #[tokio::test]
+    // faults are set per version and method while the simulator runs
+    async fn simulator_test() {
+        use protos::api::admin::fault_admin_client::FaultAdminClient;
+        use protos::api::admin::{
+            latency, ClearFaultsRequest, ErrorRate, Fault, Latency, ListFaultsRequest,
+            SetFaultRequest,
+        };
+        use protos::simulator::Faults;
+        use tonic::Code;
+        use versioning_grpc::simulator_router;
+
+        let inner_service = VectorHandler {
+            name: "simulated".to_string(),
+        };
+        let router = simulator_router(inner_service, &Faults::new(), ServeOptions::default()).await;
+        let server = TestServer::start_router(router).await.unwrap();
+        let channel = server.channel();
+        let mut admin = FaultAdminClient::new(channel.clone());
+        let mut client_v1 =
+            VectorServiceClient::new_versioned(channel.clone(), SupportedVersion::V1);
+        let mut client_v2 = VectorServiceClient::new_versioned(channel, SupportedVersion::V2);
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id".to_string(),
+                values: vec![1., 2.],
+            }],
+        };
+        let set = |fault: Fault| {
+            let mut admin = admin.clone();
+            async move {
+                admin
+                    .set_fault(SetFaultRequest { fault: Some(fault) })
+                    .await
+            }
+        };
+
+        // only V1 sums fail
+        set(Fault {
+            version: "V1".to_string(),
+            method: "Sum".to_string(),
+            errors: vec![ErrorRate {
+                code: Code::Unavailable as i32,
+                probability: 1.,
+            }],
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let status = client_v1.sum(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Unavailable);
+        client_v2.sum(request()).await.unwrap();
+        client_v1.print(PrintRequest::default()).await.unwrap();
+
+        // the V2 fault doesn't replace the more specific V1 sum fault
+        set(Fault {
+            version: "V2".to_string(),
+            latency: Some(Latency {
+                distribution: Some(latency::Distribution::FixedMs(200)),
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let started = tokio::time::Instant::now();
+        client_v2.sum(request()).await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(200));
+
+        set(Fault {
+            method: "Print".to_string(),
+            truncate_probability: 1.,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let status = client_v1.print(PrintRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::Internal);
+
+        set(Fault {
+            version: "V2".to_string(),
+            method: "Print".to_string(),
+            reset_probability: 1.,
+            ..Default::default()
+        })
+        .await
+        .unwrap();
+        let status = client_v2.print(PrintRequest::default()).await.unwrap_err();
+        // the client only sees the reset stream
+        assert_eq!(status.code(), Code::Internal);
+        assert!(status.message().contains("stream error"));
+
+        let status = set(Fault {
+            version: "V3".to_string(),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+        let status = set(Fault {
+            latency: Some(Latency {
+                distribution: Some(latency::Distribution::ExponentialMeanMs(u64::MAX)),
+            }),
+            ..Default::default()
+        })
+        .await
+        .unwrap_err();
+        assert_eq!(status.code(), Code::InvalidArgument);
+
+        let faults = admin
+            .list_faults(ListFaultsRequest {})
+            .await
+            .unwrap()
+            .into_inner()
+            .faults;
+        assert_eq!(faults.len(), 4);
+        assert_eq!(faults[0].method, "Sum");
+        admin.clear_faults(ClearFaultsRequest {}).await.unwrap();
+        client_v1.sum(request()).await.unwrap();
+        client_v2.print(PrintRequest::default()).await.unwrap();
+
+        drop((admin, client_v1, client_v2));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // a recording replays cleanly against the same build, and shows where another build differs
+    async fn record_replay_test() {
+        use protos::recording::{read_recording, replay, Recorder};
+        use protos::testing::MockVectorService;
+        use protos::vector_service::vector_service_server::VersionOverrides;
+        use protos::vector_service::SumResponse;
+        use versioning_grpc::recorded_router;
+
+        let path =
+            std::env::temp_dir().join(format!("record_replay_test_{}.bin", std::process::id()));
+        let recorder = Recorder::create(&path).unwrap();
+        let inner_service = VectorHandler {
+            name: "recorded".to_string(),
+        };
+        let router = recorded_router(inner_service, &recorder, ServeOptions::default()).await;
+        let server = TestServer::start_router(router).await.unwrap();
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let mut client_v2 = server.client(SupportedVersion::V2);
+        let vector = |values: Vec<f32>| Vector {
+            id: "id".to_string(),
+            values,
+        };
+        // the server doesn't check them, the recording must still leave them out
+        let mut request = tonic::Request::new(SumRequest {
+            vectors: vec![vector(vec![1., 2.])],
+        });
+        request
+            .metadata_mut()
+            .insert("authorization", "Bearer secret-token".parse().unwrap());
+        request
+            .metadata_mut()
+            .insert("x-api-key", "secret-key".parse().unwrap());
+        client_v1.sum(request).await.unwrap();
+        client_v2
+            .sum(SumRequest {
+                vectors: vec![vector(vec![1., 2.]), vector(vec![3., 4.])],
+            })
+            .await
+            .unwrap();
+        client_v1
+            .print(PrintRequest {
+                vector: Some(vector(vec![5.])),
+            })
+            .await
+            .unwrap();
+        drop((client_v1, client_v2));
+        server.shutdown().await.unwrap();
+
+        let calls = read_recording(&path).unwrap();
+        let recorded: Vec<_> = calls
+            .iter()
+            .map(|call| (call.version.as_str(), call.method.as_str(), call.code))
+            .collect();
+        assert_eq!(
+            recorded,
+            vec![("V1", "Sum", 0), ("V2", "Sum", 0), ("V1", "Print", 0)]
+        );
+        assert!(calls[0]
+            .metadata
+            .iter()
+            .any(|entry| entry.key == "content-type" && entry.value == "application/grpc"));
+        assert!(!calls[0].metadata.iter().any(|entry| {
+            ["authorization", "x-api-key"].contains(&entry.key.as_str())
+                || entry.value.contains("secret")
+        }));
+        assert!(!calls[0].request.is_empty());
+
+        let same_build = TestServer::start(
+            VectorHandler {
+                name: "replayed".to_string(),
+            },
+            overrides(),
+        )
+        .await
+        .unwrap();
+        let report = replay(&calls, same_build.channel()).await;
+        assert!(report.is_clean(), "{report}");
+        assert_eq!(report.versions["V1"].calls, 2);
+
+        // a build that sums differently, and can't print
+        let mock = MockVectorService::new();
+        mock.push_sum(Ok(SumResponse { sum: vec![3.] }))
+            .push_sum(Ok(SumResponse { sum: vec![3., 0.] }));
+        let other_build = TestServer::start_in_memory(mock, VersionOverrides::default())
+            .await
+            .unwrap();
+        let report = replay(&calls, other_build.channel()).await;
+        assert!(!report.is_clean());
+        let differences: Vec<_> = report
+            .versions
+            .values()
+            .flat_map(|version| &version.differences)
+            .map(|difference| (difference.index, difference.method.as_str()))
+            .collect();
+        assert_eq!(differences, vec![(2, "Print"), (1, "Sum")]);
+        assert!(report.to_string().contains("replayed FailedPrecondition"));
+
+        let _ = std::fs::remove_file(path);
+    }
+
+    #[tokio::test]
+    // the shadow sees every version's calls, only the primary answers them
+    async fn shadow_test() {
+        use protos::testing::MockVectorService;
+        use protos::vector_service::vector_service_server::{ShadowConfig, Shadowed};
+        use protos::vector_service::SumResponse;
+
+        let shadow = MockVectorService::new();
+        shadow
+            // within the tolerance of the primary's 3
+            .push_sum(Ok(SumResponse { sum: vec![3.000_1] }))
+            .push_sum(Ok(SumResponse { sum: vec![3., 8.] }));
+        let shadowed = Shadowed::new(
+            VectorHandler {
+                name: "primary".to_string(),
+            },
+            shadow.clone(),
+            ShadowConfig::default(),
+        );
+        let metrics = shadowed.metrics();
+        let server = TestServer::start_in_memory(shadowed, overrides())
+            .await
+            .unwrap();
+        let vector = |values: Vec<f32>| Vector {
+            id: "id".to_string(),
+            values,
+        };
+
+        let response = server
+            .client(SupportedVersion::V1)
+            .sum(SumRequest {
+                vectors: vec![vector(vec![1., 2.])],
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().sum, vec![3.]);
+        let mut request = tonic::Request::new(SumRequest {
+            vectors: vec![vector(vec![1., 2.]), vector(vec![3., 4.])],
+        });
+        request.set_timeout(Duration::from_secs(10));
+        let response = server
+            .client(SupportedVersion::V2)
+            .sum(request)
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().sum, vec![3., 7.]);
+        // the unscripted shadow fails, the primary still answers
+        server
+            .client(SupportedVersion::V2)
+            .print(PrintRequest {
+                vector: Some(vector(vec![1.])),
+            })
+            .await
+            .unwrap();
+
+        // comparisons finish in the background
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while metrics.compared() < 3 {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the shadow calls were never compared");
+        assert_eq!(metrics.diverged(), 2);
+        let methods: Vec<_> = metrics
+            .latest_divergences()
+            .into_iter()
+            .map(|divergence| divergence.method)
+            .collect();
+        assert_eq!(methods.len(), 2);
+        assert!(methods.contains(&"sum") && methods.contains(&"print"));
+        let requests = shadow.sum_requests();
+        let versions: Vec<_> = requests
+            .iter()
+            .map(|recorded| recorded.version.unwrap().name)
+            .collect();
+        assert_eq!(versions, vec!["V1", "V2"]);
+        // the shadow is held to the caller's deadline
+        assert!(requests[0].deadline.is_none());
+        assert!(requests[1].deadline.is_some());
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    // API keys and bearer tokens are checked against the version and method they call
+    async fn auth_test() {
+        use jsonwebtoken::{encode, EncodingKey, Header};
+        use protos::auth::{Authenticator, API_KEY_HEADER};
+        use protos::testing::MockVectorService;
+        use protos::vector_service::{PrintResponse, SumResponse};
+        use std::time::{SystemTime, UNIX_EPOCH};
+        use tonic::Code;
+        use tonic_health::pb::health_client::HealthClient;
+        use tonic_health::pb::HealthCheckRequest;
+
+        #[derive(serde::Serialize)]
+        struct Claims {
+            sub: String,
+            scope: String,
+            exp: u64,
+        }
+
+        let id = std::process::id();
+        let directory = std::env::temp_dir();
+        let keys_path = directory.join(format!("auth_test_{id}_keys.toml"));
+        let jwks_path = directory.join(format!("auth_test_{id}_jwks.json"));
+        std::fs::write(
+            &keys_path,
+            "[[keys]]\nkey = \"reporting-key\"\nprincipal = \"reporting\"\nallow = [\"V2.Sum\"]\n",
+        )
+        .unwrap();
+        let secret = b"0123456789abcdef0123456789abcdef";
+        std::fs::write(
+            &jwks_path,
+            r#"{"keys": [{"kty": "oct", "kid": "test", "alg": "HS256", "k": "MDEyMzQ1Njc4OWFiY2RlZjAxMjM0NTY3ODlhYmNkZWY"}]}"#,
+        )
+        .unwrap();
+        let authenticator = Authenticator::new()
+            .with_keys_file(&keys_path)
+            .unwrap()
+            .with_jwks_file(&jwks_path)
+            .unwrap();
+
+        let mock = MockVectorService::new();
+        let options = ServeOptions {
+            authenticator: Some(authenticator),
+            ..Default::default()
+        };
+        let server = TestServer::start_with(mock.clone(), overrides(), options)
+            .await
+            .unwrap();
+        let channel = server.channel();
+        let client = |version: SupportedVersion, header: &'static str, value: String| {
+            VectorServiceClient::with_interceptor_versioned(
+                channel.clone(),
+                move |mut request: tonic::Request<()>| {
+                    if !header.is_empty() {
+                        request
+                            .metadata_mut()
+                            .insert(header, value.parse().unwrap());
+                    }
+                    Ok(request)
+                },
+                version,
+            )
+        };
+        let token = |scope: &str, expires_in: i64| {
+            let now = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap()
+                .as_secs();
+            let claims = Claims {
+                sub: "operator".to_string(),
+                scope: scope.to_string(),
+                exp: now.saturating_add_signed(expires_in),
+            };
+            let header = Header {
+                kid: Some("test".to_string()),
+                ..Default::default()
+            };
+            let token = encode(&header, &claims, &EncodingKey::from_secret(secret)).unwrap();
+            format!("Bearer {token}")
+        };
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id".to_string(),
+                values: vec![1.],
+            }],
+        };
+
+        let status = client(SupportedVersion::V2, "", String::new())
+            .sum(request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+        let status = client(SupportedVersion::V2, API_KEY_HEADER, "wrong".to_string())
+            .sum(request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // the reporting key may sum through V2, and nothing else
+        mock.push_sum(Ok(SumResponse { sum: vec![1.] }));
+        let reporting = |version| client(version, API_KEY_HEADER, "reporting-key".to_string());
+        reporting(SupportedVersion::V2)
+            .sum(request())
+            .await
+            .unwrap();
+        let status = reporting(SupportedVersion::V2)
+            .print(PrintRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = reporting(SupportedVersion::V1)
+            .sum(request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+
+        mock.push_print(Ok(PrintResponse { printed_count: 0 }));
+        client(SupportedVersion::V1, "authorization", token("V1.*", 60))
+            .print(PrintRequest::default())
+            .await
+            .unwrap();
+        let status = client(SupportedVersion::V2, "authorization", token("V1.*", 60))
+            .sum(request())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::PermissionDenied);
+        let status = client(SupportedVersion::V1, "authorization", token("*", -600))
+            .print(PrintRequest::default())
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::Unauthenticated);
+
+        // the handler sees who called
+        let principal = mock.sum_requests()[0].principal.clone().unwrap();
+        assert_eq!(principal.name, "reporting");
+        let principal = mock.print_requests()[0].principal.clone().unwrap();
+        assert_eq!(principal.name, "operator");
+        assert_eq!(principal.permissions[0].to_string(), "V1.*");
+
+        // health checks stay open
+        HealthClient::new(channel.clone())
+            .check(HealthCheckRequest {
+                service: SupportedVersion::V1.service_name().to_string(),
+            })
+            .await
+            .unwrap();
+
+        drop(channel);
+        server.shutdown().await.unwrap();
+        let _ = std::fs::remove_file(keys_path);
+        let _ = std::fs::remove_file(jwks_path);
+    }
+
+    #[tokio::test]
+    #[allow(clippy::result_large_err)]
+    // every principal gets its own buckets, and V1 is limited more strictly than the rest
+    async fn rate_limit_test() {
+        use protos::auth::{Authenticator, Permission, Principal, API_KEY_HEADER};
+        use protos::rate_limit::{RateLimit, RateLimiter, RATE_LIMITED_REASON, RETRY_AFTER_HEADER};
+        use protos::vector_service::vector_service_client::{Method, VectorError};
+        use tonic::Code;
+
+        let principal = |name: &str| Principal {
+            name: name.to_string(),
+            permissions: vec!["*".parse::<Permission>().unwrap()],
+        };
+        let authenticator = Authenticator::new()
+            .with_key("key-a", principal("a"))
+            .with_key("key-b", principal("b"));
+        let rate_limiter = RateLimiter::new()
+            .limit(
+                None,
+                None,
+                RateLimit {
+                    per_second: 100.,
+                    burst: 100,
+                },
+            )
+            .limit(
+                SupportedVersion::V1,
+                None,
+                RateLimit {
+                    per_second: 0.5,
+                    burst: 2,
+                },
+            );
+        let metrics = rate_limiter.metrics();
+        let inner_service = VectorHandler {
+            name: "limited".to_string(),
+        };
+        let options = ServeOptions {
+            authenticator: Some(authenticator),
+            rate_limiter: Some(rate_limiter),
+            ..Default::default()
+        };
+        let server = TestServer::start_with(inner_service, overrides(), options)
+            .await
+            .unwrap();
+        let channel = server.channel();
+        let client = |version: SupportedVersion, key: &'static str| {
+            VectorServiceClient::with_interceptor_versioned(
+                channel.clone(),
+                move |mut request: tonic::Request<()>| {
+                    request
+                        .metadata_mut()
+                        .insert(API_KEY_HEADER, key.parse().unwrap());
+                    Ok(request)
+                },
+                version,
+            )
+        };
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id".to_string(),
+                values: vec![1.],
+            }],
+        };
+
+        let mut client_a = client(SupportedVersion::V1, "key-a");
+        client_a.sum(request()).await.unwrap();
+        client_a.sum(request()).await.unwrap();
+        let status = client_a.sum(request()).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+        assert_eq!(status.metadata().get(RETRY_AFTER_HEADER).unwrap(), "2");
+        let error = VectorError::from(status);
+        assert!(error.retry_after().unwrap() > Duration::from_secs(1));
+        assert!(
+            matches!(error, VectorError::ResourceExhausted { reason, .. } if reason == RATE_LIMITED_REASON)
+        );
+        // the limit covers all of V1, not just sums
+        let status = client_a.print(PrintRequest::default()).await.unwrap_err();
+        assert_eq!(status.code(), Code::ResourceExhausted);
+
+        client(SupportedVersion::V1, "key-b")
+            .sum(request())
+            .await
+            .unwrap();
+        for _ in 0..10 {
+            client(SupportedVersion::V2, "key-a")
+                .sum(request())
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(metrics.allowed(SupportedVersion::V1, Method::Sum), 3);
+        assert_eq!(metrics.rejected(SupportedVersion::V1, Method::Sum), 1);
+        assert_eq!(metrics.rejected(SupportedVersion::V1, Method::Print), 1);
+        assert_eq!(metrics.allowed(SupportedVersion::V2, Method::Sum), 10);
+
+        drop((client_a, channel));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // V1 runs one call at a time with one more waiting, anything past that is shed
+    // while V2 keeps answering
+    async fn concurrency_test() {
+        use protos::concurrency::{ConcurrencyLimit, ConcurrencyLimits, LimitConfig};
+        use protos::testing::MockVectorService;
+        use protos::vector_service::SumResponse;
+        use tonic::Code;
+
+        let mock = MockVectorService::new();
+        mock.set_latency(Duration::from_millis(500));
+        for _ in 0..3 {
+            mock.push_sum(Ok(SumResponse { sum: vec![1.] }));
+        }
+        let limits = ConcurrencyLimits::new()
+            .global(LimitConfig {
+                limit: ConcurrencyLimit::Fixed { max_in_flight: 10 },
+                max_queue: 0,
+            })
+            .unwrap()
+            .version(
+                SupportedVersion::V1,
+                LimitConfig {
+                    limit: ConcurrencyLimit::Fixed { max_in_flight: 1 },
+                    max_queue: 1,
+                },
+            )
+            .unwrap();
+        let options = ServeOptions {
+            concurrency: limits.clone(),
+            ..Default::default()
+        };
+        let server = TestServer::start_with(mock, overrides(), options)
+            .await
+            .unwrap();
+        let calls: Vec<_> = [
+            SupportedVersion::V1,
+            SupportedVersion::V1,
+            SupportedVersion::V1,
+            SupportedVersion::V2,
+        ]
+        .into_iter()
+        .map(|version| {
+            let mut client = server.client(version);
+            tokio::spawn(async move {
+                let request = SumRequest {
+                    vectors: vec![Vector {
+                        id: "id".to_string(),
+                        values: vec![1.],
+                    }],
+                };
+                client.sum(request).await.map(|_| ())
+            })
+        })
+        .collect();
+        sleep(Duration::from_millis(250)).await;
+
+        let v1 = limits.version_stats(SupportedVersion::V1).unwrap();
+        assert_eq!((v1.in_flight, v1.queued, v1.shed), (1, 1, 1));
+        assert!(limits.version_stats(SupportedVersion::V2).is_none());
+        // the waiting V1 call holds a global slot as well
+        assert_eq!(limits.global_stats().unwrap().in_flight, 3);
+
+        let mut results = Vec::new();
+        for call in calls {
+            results.push(call.await.unwrap());
+        }
+        let shed = results
+            .iter()
+            .filter(|result| matches!(result, Err(status) if status.code() == Code::Unavailable))
+            .count();
+        assert_eq!(shed, 1);
+        assert!(results[3].is_ok());
+        assert_eq!(results.iter().filter(|result| result.is_ok()).count(), 3);
+        let v1 = limits.version_stats(SupportedVersion::V1).unwrap();
+        assert_eq!((v1.in_flight, v1.queued), (0, 0));
+        assert_eq!(limits.global_stats().unwrap().in_flight, 0);
+
+        server.shutdown().await.unwrap();
+    }
+
+    #[test]
+    // limits that could shut every call out are rejected when they are set
+    fn concurrency_config_test() {
+        use protos::concurrency::{ConcurrencyLimit, ConcurrencyLimits, ConfigError, LimitConfig};
+
+        let adaptive = |initial, min, max| ConcurrencyLimit::Adaptive {
+            initial,
+            min,
+            max,
+            target_latency: Duration::from_millis(100),
+        };
+        let rejected = [
+            (
+                ConcurrencyLimit::Fixed { max_in_flight: 0 },
+                "a fixed concurrency limit must let at least one call in",
+            ),
+            (
+                adaptive(1, 0, 10),
+                "an adaptive concurrency limit must let at least one call in",
+            ),
+            (
+                adaptive(1, 2, 10),
+                "an adaptive concurrency limit must start within 2..=10, not at 1",
+            ),
+            (
+                adaptive(11, 2, 10),
+                "an adaptive concurrency limit must start within 2..=10, not at 11",
+            ),
+        ];
+        for (limit, message) in rejected {
+            let config = LimitConfig {
+                limit,
+                max_queue: 0,
+            };
+            let error = ConcurrencyLimits::new().global(config).unwrap_err();
+            assert_eq!(error, ConfigError(message.to_string()));
+        }
+        let limits = ConcurrencyLimits::new()
+            .version(
+                SupportedVersion::V1,
+                LimitConfig {
+                    limit: adaptive(1, 1, 10),
+                    max_queue: 0,
+                },
+            )
+            .unwrap();
+        assert_eq!(limits.version_stats(SupportedVersion::V1).unwrap().limit, 1);
+    }
+
+    #[tokio::test]
+    // violations come back as BadRequest details, with the field paths of the caller's version
+    async fn validation_test() {
+        use protos::validation::{NonFinitePolicy, ValidationConfig};
+        use regex::Regex;
+        use tonic::Code;
+        use tonic_types::StatusExt;
+
+        let validation = ValidationConfig {
+            max_vectors: Some(2),
+            dimension: Some(2),
+            max_id_length: Some(8),
+            id_pattern: Some(Regex::new("^[a-z0-9-]+$").unwrap()),
+            non_finite: NonFinitePolicy::Reject,
+            ..Default::default()
+        };
+        let inner_service = VectorHandler {
+            name: "validated".to_string(),
+        };
+        let options = ServeOptions {
+            validation,
+            ..Default::default()
+        };
+        let server = TestServer::start_with(inner_service, overrides(), options)
+            .await
+            .unwrap();
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let mut client_v2 = server.client(SupportedVersion::V2);
+        let vector = |id: &str, values: Vec<f32>| Vector {
+            id: id.to_string(),
+            values,
+        };
+        let violations = |status: tonic::Status| {
+            assert_eq!(status.code(), Code::InvalidArgument);
+            status
+                .get_details_bad_request()
+                .unwrap()
+                .field_violations
+                .into_iter()
+                .map(|violation| violation.field)
+                .collect::<Vec<_>>()
+        };
+
+        let status = client_v1
+            .sum(SumRequest {
+                vectors: vec![vector("a", vec![1., f32::NAN])],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.message(), "invalid request: 1 field violation");
+        assert_eq!(violations(status), vec!["vector.values[1]"]);
+
+        let status = client_v2
+            .sum(SumRequest {
+                vectors: vec![
+                    vector("a", vec![1., 2.]),
+                    vector("Not_An_Id", vec![1., f32::INFINITY]),
+                    vector("c", vec![1.]),
+                ],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            violations(status),
+            vec![
+                "vectors",
+                "vectors[1].id",
+                "vectors[1].id",
+                "vectors[1].values[1]",
+                "vectors[2].values",
+            ]
+        );
+
+        let response = client_v2
+            .sum(SumRequest {
+                vectors: vec![vector("a", vec![1., 2.]), vector("b-2", vec![3., 4.])],
+            })
+            .await
+            .unwrap();
+        assert_eq!(response.into_inner().sum, vec![3., 7.]);
+
+        // print's vector is held to the same limits
+        let status = client_v1
+            .print(PrintRequest {
+                vector: Some(vector("Not_An_Id", vec![1.])),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(
+            violations(status),
+            vec!["vector.id", "vector.id", "vector.values"]
+        );
+
+        drop((client_v1, client_v2));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // typed errors reach versioned clients with their details, BadRequest paths in the caller's version
+    async fn error_model_test() {
+        use protos::errors::{FieldViolation, PreconditionViolation, SumFields};
+        use protos::testing::MockVectorService;
+        use protos::validation::{NonFinitePolicy, ValidationConfig};
+        use protos::vector_service::vector_service_client::VectorError;
+        use protos::vector_service::vector_service_server::VersionOverrides;
+        use tonic::Code;
+
+        let v1_fields = SupportedVersion::V1.sum_fields();
+        assert_eq!(v1_fields.from_inner("vectors[2].values"), "vector.values");
+        assert_eq!(v1_fields.from_inner("vectors"), "vector");
+        assert_eq!(v1_fields.from_inner("vectorsize"), "vectorsize");
+        let v2_fields = SupportedVersion::V2.sum_fields();
+        assert_eq!(
+            v2_fields,
+            SumFields {
+                vectors: "vectors",
+                repeated: true
+            }
+        );
+        assert_eq!(
+            v2_fields.from_inner("vectors[2].values"),
+            "vectors[2].values"
+        );
+
+        let mock = MockVectorService::new();
+        let server = TestServer::start_in_memory(mock.clone(), VersionOverrides::default())
+            .await
+            .unwrap();
+        let request = || SumRequest {
+            vectors: vec![Vector {
+                id: "id".to_string(),
+                values: vec![1.],
+            }],
+        };
+        let bad_request = || {
+            VectorError::invalid_argument(vec![FieldViolation::new(
+                "vectors[0].values",
+                "must not be empty",
+            )])
+        };
+        mock.push_sum(Err(bad_request().into()))
+            .push_sum(Err(bad_request().into()))
+            .push_sum(Err(VectorError::FailedPrecondition {
+                message: "the index is not built yet".to_string(),
+                violations: vec![PreconditionViolation::new("INDEX", "default", "building")],
+            }
+            .into()))
+            .push_sum(Err(VectorError::Unavailable {
+                reason: "MAINTENANCE".to_string(),
+                message: "down for maintenance".to_string(),
+                retry_after: Some(Duration::from_secs(30)),
+            }
+            .into()));
+
+        let field = |error: VectorError| match error {
+            VectorError::InvalidArgument { violations, .. } => violations[0].field.clone(),
+            error => panic!("expected a BadRequest, got {error}"),
+        };
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let mut client_v2 = server.client(SupportedVersion::V2);
+        let error = client_v1.try_sum(request()).await.unwrap_err();
+        assert_eq!(field(error), "vector.values");
+        let error = client_v2.try_sum(request()).await.unwrap_err();
+        assert_eq!(field(error), "vectors[0].values");
+
+        match client_v2.try_sum(request()).await.unwrap_err() {
+            VectorError::FailedPrecondition {
+                message,
+                violations,
+            } => {
+                assert_eq!(message, "the index is not built yet");
+                assert_eq!(violations[0].subject, "default");
+            }
+            error => panic!("expected a PreconditionFailure, got {error}"),
+        }
+        let error = client_v1.try_sum(request()).await.unwrap_err();
+        assert_eq!(error.code(), Code::Unavailable);
+        assert_eq!(error.retry_after(), Some(Duration::from_secs(30)));
+        assert!(
+            matches!(error, VectorError::Unavailable { reason, .. } if reason == "MAINTENANCE")
+        );
+        drop((client_v1, client_v2));
+        server.shutdown().await.unwrap();
+
+        // a fanned-out sum fails with the violations of its failed call, naming the vector it sent
+        let options = ServeOptions {
+            validation: ValidationConfig {
+                non_finite: NonFinitePolicy::Reject,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let inner_service = VectorHandler {
+            name: "fanned-out".to_string(),
+        };
+        let server = TestServer::start_in_memory_with(inner_service, overrides(), options)
+            .await
+            .unwrap();
+        let mut client_v1 = server.client(SupportedVersion::V1);
+        let vector = |values: Vec<f32>| Vector {
+            id: "id".to_string(),
+            values,
+        };
+        let error = client_v1
+            .try_sum(SumRequest {
+                vectors: vec![vector(vec![1.]), vector(vec![1., f32::NAN])],
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(error.code(), Code::InvalidArgument);
+        assert_eq!(field(error), "vectors[1].values[1]");
+
+        drop(client_v1);
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // non-finite values are summed as zero, or passed through untouched
+    async fn non_finite_policy_test() {
+        use protos::validation::{NonFinitePolicy, Validated, ValidationConfig};
+        use protos::vector_service::vector_service_server::VersionOverrides;
+
+        let sum = |policy: NonFinitePolicy| async move {
+            let validated = Validated::new(
+                VectorHandler {
+                    name: "non-finite".to_string(),
+                },
+                ValidationConfig {
+                    non_finite: policy,
+                    ..Default::default()
+                },
+            );
+            let server = TestServer::start_in_memory(validated, VersionOverrides::default())
+                .await
+                .unwrap();
+            let request = SumRequest {
+                vectors: vec![Vector {
+                    id: "id".to_string(),
+                    values: vec![1., f32::NAN, f32::NEG_INFINITY, 2.],
+                }],
+            };
+            server
+                .client(SupportedVersion::V2)
+                .sum(request)
+                .await
+                .unwrap()
+                .into_inner()
+                .sum[0]
+        };
+
+        assert_eq!(sum(NonFinitePolicy::Ignore).await, 3.);
+        assert!(sum(NonFinitePolicy::Propagate).await.is_nan());
+    }
+
+    #[tokio::test]
+    // the inner service sees the caller's deadline, and its token is cancelled once the call is dropped
+    async fn deadline_test() {
+        use protos::api::v2::vector_service_server::VectorService as VectorService_V2;
+        use protos::testing::MockVectorService;
+        use protos::vector_service::vector_service_server::{VersionAdapter, VersionOverrides};
+        use protos::vector_service::SumResponse;
+        use tonic::Code;
+
+        let mock = MockVectorService::new();
+        mock.push_sum(Ok(SumResponse { sum: vec![1.] }))
+            .push_sum(Ok(SumResponse { sum: vec![1.] }));
+        let server = TestServer::start(mock.clone(), overrides()).await.unwrap();
+        let mut client = server.client(SupportedVersion::V2);
+        let request = |timeout: Duration| {
+            let mut request = tonic::Request::new(SumRequest {
+                vectors: vec![Vector {
+                    id: "id".to_string(),
+                    values: vec![1.],
+                }],
+            });
+            request.set_timeout(timeout);
+            request
+        };
+
+        client.sum(request(Duration::from_secs(5))).await.unwrap();
+        let remaining = mock.sum_requests()[0].deadline.unwrap().remaining();
+        assert!(remaining > Duration::from_secs(4) && remaining <= Duration::from_secs(5));
+        assert!(mock.sum_requests()[0]
+            .cancellation
+            .as_ref()
+            .unwrap()
+            .is_cancelled());
+
+        mock.set_latency(Duration::from_secs(5));
+        let status = client
+            .sum(request(Duration::from_millis(200)))
+            .await
+            .unwrap_err();
+        // whichever of tonic's timeout and the adapter's fired first
+        assert!(matches!(
+            status.code(),
+            Code::DeadlineExceeded | Code::Cancelled
+        ));
+        let cancellation = mock.sum_requests()[1].cancellation.clone().unwrap();
+        tokio::time::timeout(Duration::from_secs(1), cancellation.cancelled())
+            .await
+            .unwrap();
+
+        // out of time by the time it reaches the adapter, the inner service never sees it
+        let adapter = VersionAdapter::new(mock.clone(), VersionOverrides::default());
+        let status = VectorService_V2::sum(&adapter, request(Duration::ZERO))
+            .await
+            .unwrap_err();
+        assert_eq!(status.code(), Code::DeadlineExceeded);
+        assert_eq!(mock.sum_requests().len(), 2);
+
+        drop(client);
+        server.shutdown().await.unwrap();
     }
 
     use protos::actual_clients::v1::Vector as Vector_V1;
@@ -87,22 +1984,14 @@ mod tests {
     #[tokio::test]
     // in this test we will use the actual clients the users will be using
     async fn actual_client_test() {
-        let port = 1818;
-
-        let address: Uri = format!("https://0.0.0.0:{}", port).parse().unwrap();
-        println!("Connecting to {}", address);
         let inner_service = VectorHandler {
             name: "actual_input".to_string(),
         };
-        let server_handle = tokio::spawn(async move {
-            let _ = serve(port, inner_service.clone()).await;
-        });
-        sleep(Duration::from_secs(1)).await;
+        let server = TestServer::start(inner_service, overrides()).await.unwrap();
+        println!("Connecting to {:?}", server.local_addr());
 
-        let mut client_v1 = VectorServiceClient_V1::connect(address.clone())
-            .await
-            .unwrap();
-        let mut client_v2 = VectorServiceClient_V2::connect(address).await.unwrap();
+        let mut client_v1 = VectorServiceClient_V1::new(server.channel());
+        let mut client_v2 = VectorServiceClient_V2::new(server.channel());
 
         let vec1 = Vector_V1 {
             id: "id1".parse().unwrap(),
@@ -124,12 +2013,112 @@ mod tests {
         let sum_result2 = client_v2.sum(sum_request_2).await;
         println!("sum result 2: {sum_result2:?}");
 
-        server_handle.abort();
-        // Wait for the server to shut down
-        let _ = server_handle.await;
+        drop((client_v1, client_v2));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // V1 keeps returning 0.0 for an empty sum through an override, V2 goes through the inner service
+    async fn legacy_override_test() {
+        let inner_service = VectorHandler {
+            name: "legacy".to_string(),
+        };
+        let server = TestServer::start(inner_service, overrides()).await.unwrap();
+
+        let mut client_v1 = VectorServiceClient_V1::new(server.channel());
+        let mut client_v2 = VectorServiceClient_V2::new(server.channel());
+
+        let empty_sum = client_v1.sum(SumRequest_V1 { vector: None }).await.unwrap();
+        assert_eq!(empty_sum.into_inner().sum, 0.0);
+
+        let sum = client_v1
+            .sum(SumRequest_V1 {
+                vector: Some(Vector_V1 {
+                    id: "id1".to_string(),
+                    values: vec![1., 2.],
+                }),
+            })
+            .await
+            .unwrap();
+        assert_eq!(sum.into_inner().sum, 3.0);
+
+        let empty_sum = client_v2
+            .sum(SumRequest_V2 { vectors: vec![] })
+            .await
+            .unwrap();
+        assert!(empty_sum.into_inner().sum.is_empty());
+
+        drop((client_v1, client_v2));
+        server.shutdown().await.unwrap();
+    }
+
+    #[tokio::test]
+    // V1 calls go through the translation proxy and are served by the upstream's V2 service
+    async fn proxy_test() {
+        use protos::validation::{NonFinitePolicy, ValidationConfig};
+        use tonic_types::StatusExt;
+
+        let inner_service = VectorHandler {
+            name: "proxied".to_string(),
+        };
+        let options = ServeOptions {
+            validation: ValidationConfig {
+                non_finite: NonFinitePolicy::Reject,
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let server = TestServer::start_with(inner_service, overrides(), options)
+            .await
+            .unwrap();
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let proxy_addr = listener.local_addr().unwrap();
+        let mut config = ProxyConfig::from_file("proxy/v1_to_v2.toml").unwrap();
+        config.listen = proxy_addr;
+        config.upstream = format!("http://{}", server.local_addr().unwrap());
+        // the listener is bound already, connecting doesn't race the proxy task
+        let proxy_handle = tokio::spawn(async move {
+            let _ = serve_proxy_on(listener, config).await;
+        });
+
+        let mut client_v1 = VectorServiceClient_V1::connect(format!("http://{proxy_addr}"))
+            .await
+            .unwrap();
+        let response = client_v1
+            .sum(SumRequest_V1 {
+                vector: Some(Vector_V1 {
+                    id: "id1".to_string(),
+                    values: vec![1., 2., 3.],
+                }),
+            })
+            .await
+            .unwrap();
+        // the caller sees the version it called, not the upstream's
+        assert_eq!(response.metadata().get(API_VERSION_HEADER).unwrap(), "V1");
+        assert_eq!(response.into_inner().sum, 6.0);
+
+        // the upstream's violations name the caller's fields
+        let status = client_v1
+            .sum(SumRequest_V1 {
+                vector: Some(Vector_V1 {
+                    id: "id1".to_string(),
+                    values: vec![1., f32::NAN],
+                }),
+            })
+            .await
+            .unwrap_err();
+        assert_eq!(status.metadata().get(API_VERSION_HEADER).unwrap(), "V1");
+        let violations = status.get_details_bad_request().unwrap().field_violations;
+        assert_eq!(violations[0].field, "vector.values[1]");
+
+        proxy_handle.abort();
+        let _ = proxy_handle.await;
+        drop(client_v1);
+        server.shutdown().await.unwrap();
     }
 
     #[tokio::test]
+    #[ignore = "needs the server started by hand on port 1620, `cargo run` in another terminal"]
     // in this test we will rely on the server running in a different terminal.
     // this may help simplify what happens on the which end (client/server)
     async fn detached_test() {
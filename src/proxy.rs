@@ -0,0 +1,388 @@
+// a version translation proxy: accepts calls made against an old API version and forwards them to an
+// upstream that only speaks a newer one. messages are translated at runtime from the descriptor sets
+// and a declarative mapping file, so no compiled-in types of either version are needed.
+
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt;
+use std::net::{SocketAddr, TcpListener};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context};
+use hyper::service::{make_service_fn, service_fn};
+use prost::bytes::{Buf, BufMut, Bytes};
+use prost::Message;
+use prost_reflect::{
+    DescriptorPool, DynamicMessage, FieldDescriptor, Kind, MessageDescriptor, Value,
+};
+use serde::Deserialize;
+use tonic::body::BoxBody;
+use tonic::codec::{Codec, DecodeBuf, Decoder, EncodeBuf, Encoder};
+use tonic::codegen::http::uri::PathAndQuery;
+use tonic::codegen::{http, BoxFuture};
+use tonic::metadata::{MetadataMap, MetadataValue};
+use tonic::server::UnaryService;
+use tonic::transport::{Channel, Endpoint};
+use tonic::{Extensions, Request, Response, Status};
+
+use protos::errors::rewrite_field_paths;
+use protos::vector_service::vector_service_client::SupportedVersion;
+use protos::vector_service::API_VERSION_HEADER;
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct ProxyConfig {
+    pub listen: SocketAddr,
+    pub upstream: String,
+    // descriptor set files to load, the sets compiled by `protos` are used when empty
+    #[serde(default)]
+    pub descriptor_sets: Vec<PathBuf>,
+    pub methods: Vec<MethodMapping>,
+}
+
+// maps one old-version method onto its upstream counterpart.
+// `request` renames fields of the caller's request to the upstream request,
+// `response` renames fields of the upstream response to the caller's response.
+// fields that are not renamed are matched by name.
+#[derive(Debug, Clone, Deserialize)]
+pub struct MethodMapping {
+    pub from: String,
+    pub to: String,
+    #[serde(default)]
+    pub request: HashMap<String, String>,
+    #[serde(default)]
+    pub response: HashMap<String, String>,
+}
+
+impl ProxyConfig {
+    pub fn from_file(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("error reading proxy config {path:?}"))?;
+        toml::from_str(&content).with_context(|| format!("error parsing proxy config {path:?}"))
+    }
+
+    fn descriptor_pool(&self) -> anyhow::Result<DescriptorPool> {
+        let mut pool = DescriptorPool::new();
+        if self.descriptor_sets.is_empty() {
+            for descriptor_set in protos::descriptors::ALL {
+                pool.decode_file_descriptor_set(descriptor_set)?;
+            }
+        }
+        for path in &self.descriptor_sets {
+            let descriptor_set = std::fs::read(path)
+                .with_context(|| format!("error reading descriptor set {path:?}"))?;
+            pool.decode_file_descriptor_set(descriptor_set.as_slice())
+                .with_context(|| format!("error decoding descriptor set {path:?}"))?;
+        }
+        Ok(pool)
+    }
+}
+
+pub async fn serve_proxy(config: ProxyConfig) -> anyhow::Result<()> {
+    let listener = TcpListener::bind(config.listen)
+        .with_context(|| format!("error binding proxy to {}", config.listen))?;
+    serve_proxy_on(listener, config).await
+}
+
+/// Like [`serve_proxy`], accepting calls on `listener` rather than on `config.listen`.
+pub async fn serve_proxy_on(listener: TcpListener, config: ProxyConfig) -> anyhow::Result<()> {
+    let pool = config.descriptor_pool()?;
+    let upstream = Endpoint::from_shared(config.upstream.clone())?.connect_lazy();
+    let routes = config
+        .methods
+        .iter()
+        .map(|mapping| {
+            let route = Route::new(&pool, mapping, upstream.clone())?;
+            Ok((mapping.from.clone(), Arc::new(route)))
+        })
+        .collect::<anyhow::Result<HashMap<_, _>>>()?;
+    let routes = Arc::new(routes);
+
+    let make_service = make_service_fn(move |_| {
+        let routes = routes.clone();
+        async move { Ok::<_, Infallible>(service_fn(move |request| dispatch(routes.clone(), request))) }
+    });
+    hyper::Server::from_tcp(listener)
+        .context("error listening for proxied calls")?
+        .http2_only(true)
+        .serve(make_service)
+        .await
+        .context("error running proxy")
+}
+
+async fn dispatch(
+    routes: Arc<HashMap<String, Arc<Route>>>,
+    request: http::Request<hyper::Body>,
+) -> Result<http::Response<BoxBody>, Infallible> {
+    let path = request.uri().path();
+    let Some(route) = routes.get(path).cloned() else {
+        return Ok(Status::unimplemented(format!("{path} is not mapped by the proxy")).to_http());
+    };
+    let mut grpc = tonic::server::Grpc::new(BytesCodec);
+    Ok(grpc.unary(RouteService(route), request).await)
+}
+
+struct Route {
+    target_path: PathAndQuery,
+    source_input: MessageDescriptor,
+    source_output: MessageDescriptor,
+    target_input: MessageDescriptor,
+    target_output: MessageDescriptor,
+    request_renames: HashMap<String, String>,
+    response_renames: HashMap<String, String>,
+    // what the caller's answers are stamped with, `None` for a service outside the versioned API
+    source_version: Option<&'static str>,
+    upstream: Channel,
+}
+
+impl Route {
+    fn new(
+        pool: &DescriptorPool,
+        mapping: &MethodMapping,
+        upstream: Channel,
+    ) -> anyhow::Result<Self> {
+        let (source_input, source_output) = method_types(pool, &mapping.from)?;
+        let (target_input, target_output) = method_types(pool, &mapping.to)?;
+        let source_version = SupportedVersion::all()
+            .iter()
+            .find(|version| {
+                let service = mapping.from.strip_prefix('/').unwrap_or_default();
+                service.split('/').next() == Some(version.service_name())
+            })
+            .map(|version| version.api_version().name);
+        Ok(Self {
+            target_path: PathAndQuery::try_from(mapping.to.as_str())?,
+            source_input,
+            source_output,
+            target_input,
+            target_output,
+            request_renames: mapping.request.clone(),
+            response_renames: mapping.response.clone(),
+            source_version,
+            upstream,
+        })
+    }
+
+    async fn forward(&self, request: Request<Bytes>) -> Result<Response<Bytes>, Status> {
+        let (metadata, _, body) = request.into_parts();
+        let message = DynamicMessage::decode(self.source_input.clone(), body)
+            .map_err(|e| Status::invalid_argument(format!("error decoding request: {e}")))?;
+        let message = translate(&message, &self.target_input, &self.request_renames)
+            .map_err(|e| Status::invalid_argument(e.to_string()))?;
+
+        let mut grpc = tonic::client::Grpc::new(self.upstream.clone());
+        grpc.ready()
+            .await
+            .map_err(|e| Status::unavailable(format!("upstream is not ready: {e}")))?;
+        let request = Request::from_parts(
+            metadata,
+            Extensions::default(),
+            message.encode_to_vec().into(),
+        );
+        let (mut metadata, body, _) = grpc
+            .unary(request, self.target_path.clone(), BytesCodec)
+            .await
+            .map_err(|status| {
+                // violations name the fields of the upstream request
+                let mut status = rewrite_field_paths(status, |path| self.source_path(path));
+                self.stamp_version(status.metadata_mut());
+                status
+            })?
+            .into_parts();
+        self.stamp_version(&mut metadata);
+
+        let message = DynamicMessage::decode(self.target_output.clone(), body)
+            .map_err(|e| Status::internal(format!("error decoding upstream response: {e}")))?;
+        let message = translate(&message, &self.source_output, &self.response_renames)
+            .map_err(|e| Status::internal(e.to_string()))?;
+        Ok(Response::from_parts(
+            metadata,
+            message.encode_to_vec().into(),
+            Extensions::default(),
+        ))
+    }
+
+    // the upstream stamps its own version, the caller called another one
+    fn stamp_version(&self, metadata: &mut MetadataMap) {
+        match self.source_version {
+            Some(version) => {
+                metadata.insert(API_VERSION_HEADER, MetadataValue::from_static(version))
+            }
+            None => metadata.remove(API_VERSION_HEADER),
+        };
+    }
+
+    // a field path of the upstream request as one of the caller's, undoing the renames of its
+    // top-level field, e.g. `vectors[0].values` is `vector.values` when `vector` became `vectors`
+    fn source_path(&self, path: &str) -> String {
+        let (name, rest) = path.split_at(path.find(['.', '[']).unwrap_or(path.len()));
+        let name = self
+            .request_renames
+            .iter()
+            .find(|(_, target)| target.as_str() == name)
+            .map_or(name, |(source, _)| source.as_str());
+        let Some(field) = self.source_input.get_field_by_name(name) else {
+            return path.to_string();
+        };
+        // a single field that became a list upstream is that list's only element
+        let rest = match field.is_list() {
+            false => rest.strip_prefix("[0]").unwrap_or(rest),
+            true => rest,
+        };
+        format!("{name}{rest}")
+    }
+}
+
+// looks up the input and output types of a method given as a gRPC path, e.g. `/API.V1.VectorService/Sum`
+fn method_types(
+    pool: &DescriptorPool,
+    path: &str,
+) -> anyhow::Result<(MessageDescriptor, MessageDescriptor)> {
+    let (service, method) = path
+        .strip_prefix('/')
+        .and_then(|path| path.split_once('/'))
+        .ok_or_else(|| anyhow!("{path} is not a gRPC method path"))?;
+    let service = pool
+        .get_service_by_name(service)
+        .ok_or_else(|| anyhow!("unknown service {service}"))?;
+    let method = service
+        .methods()
+        .find(|candidate| candidate.name() == method)
+        .ok_or_else(|| anyhow!("unknown method {method} in {}", service.full_name()))?;
+    Ok((method.input(), method.output()))
+}
+
+#[derive(Debug)]
+pub struct TranslationError(String);
+
+impl fmt::Display for TranslationError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for TranslationError {}
+
+// builds a `target` message out of `message`, field by field. top-level fields can be renamed,
+// nested messages are matched by field name only.
+pub fn translate(
+    message: &DynamicMessage,
+    target: &MessageDescriptor,
+    renames: &HashMap<String, String>,
+) -> Result<DynamicMessage, TranslationError> {
+    let mut translated = DynamicMessage::new(target.clone());
+    for (field, value) in message.fields() {
+        let target_name = renames
+            .get(field.name())
+            .map_or(field.name(), String::as_str);
+        let target_field = target.get_field_by_name(target_name).ok_or_else(|| {
+            TranslationError(format!(
+                "{} has no counterpart in {}",
+                field.full_name(),
+                target.full_name()
+            ))
+        })?;
+        let value = translate_field(value, &field, &target_field)?;
+        translated.set_field(&target_field, value);
+    }
+    Ok(translated)
+}
+
+fn translate_field(
+    value: &Value,
+    field: &FieldDescriptor,
+    target_field: &FieldDescriptor,
+) -> Result<Value, TranslationError> {
+    if field.is_map() || target_field.is_map() {
+        return match value.is_valid_for_field(target_field) {
+            true => Ok(value.clone()),
+            false => Err(TranslationError(format!(
+                "cannot translate map {} into {}",
+                field.full_name(),
+                target_field.full_name()
+            ))),
+        };
+    }
+    let kind = target_field.kind();
+    match (value, target_field.is_list()) {
+        (Value::List(values), true) => values
+            .iter()
+            .map(|value| translate_value(value, &kind))
+            .collect::<Result<_, _>>()
+            .map(Value::List),
+        (Value::List(values), false) => match values.as_slice() {
+            [value] => translate_value(value, &kind),
+            _ => Err(TranslationError(format!(
+                "cannot narrow {} values of {} into the single {}",
+                values.len(),
+                field.full_name(),
+                target_field.full_name()
+            ))),
+        },
+        (value, true) => Ok(Value::List(vec![translate_value(value, &kind)?])),
+        (value, false) => translate_value(value, &kind),
+    }
+}
+
+fn translate_value(value: &Value, kind: &Kind) -> Result<Value, TranslationError> {
+    match (value, kind) {
+        (Value::Message(message), Kind::Message(target)) => {
+            Ok(Value::Message(translate(message, target, &HashMap::new())?))
+        }
+        (value, kind) if value.is_valid(kind) => Ok(value.clone()),
+        (value, kind) => Err(TranslationError(format!(
+            "cannot translate {value:?} into {kind:?}"
+        ))),
+    }
+}
+
+struct RouteService(Arc<Route>);
+
+impl UnaryService<Bytes> for RouteService {
+    type Response = Bytes;
+    type Future = BoxFuture<Response<Bytes>, Status>;
+
+    fn call(&mut self, request: Request<Bytes>) -> Self::Future {
+        let route = self.0.clone();
+        Box::pin(async move { route.forward(request).await })
+    }
+}
+
+// passes encoded messages through untouched, decoding happens against the descriptors instead
+#[derive(Debug, Clone, Copy, Default)]
+struct BytesCodec;
+
+impl Codec for BytesCodec {
+    type Encode = Bytes;
+    type Decode = Bytes;
+    type Encoder = BytesCodec;
+    type Decoder = BytesCodec;
+
+    fn encoder(&mut self) -> Self::Encoder {
+        BytesCodec
+    }
+
+    fn decoder(&mut self) -> Self::Decoder {
+        BytesCodec
+    }
+}
+
+impl Encoder for BytesCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn encode(&mut self, item: Bytes, dst: &mut EncodeBuf<'_>) -> Result<(), Status> {
+        dst.put(item);
+        Ok(())
+    }
+}
+
+impl Decoder for BytesCodec {
+    type Item = Bytes;
+    type Error = Status;
+
+    fn decode(&mut self, src: &mut DecodeBuf<'_>) -> Result<Option<Bytes>, Status> {
+        Ok(Some(src.copy_to_bytes(src.remaining())))
+    }
+}